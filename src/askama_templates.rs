@@ -12,10 +12,14 @@ pub struct PhraseTemplate<'a> {
     pub japanese: &'a str,
     pub prev_link: &'a str,
     pub next_link: &'a str,
+    pub graded_prev_link: &'a str,
+    pub graded_next_link: &'a str,
     pub wav_tag: &'a str,
     pub analysis: &'a str,
     pub oga_tag: &'a str,
     pub pekzep_images: &'a str,
+    pub pekzep_ruby: &'a str,
+    pub interlinear_gloss: &'a str,
     pub author_color: &'a str,
     pub author_name: &'a str,
     pub has_audio: bool,
@@ -28,6 +32,42 @@ pub struct IndTemplate<'a> {
     pub index: &'a str,
     pub length: usize,
     pub how_many_glosses: usize,
+    pub lessons: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "graded_index.html")]
+pub struct GradedIndexTemplate<'a> {
+    pub index: &'a str,
+    pub length: usize,
+    pub first_link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "lesson.html")]
+pub struct LessonTemplate<'a> {
+    pub number: usize,
+    pub title_en: &'a str,
+    pub title_zh: &'a str,
+    pub title_ja: &'a str,
+    pub prev_link: &'a str,
+    pub next_link: &'a str,
+    pub phrases: &'a str,
+    pub new_words: &'a str,
+    pub new_chars: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "worksheet.html")]
+pub struct WorksheetTemplate<'a> {
+    pub title: &'a str,
+    pub kind: &'a str,
+    pub lesson_link: &'a str,
+    pub other_link: &'a str,
+    pub other_kind: &'a str,
+    pub translate: &'a str,
+    pub fill_in: &'a str,
+    pub linzklar: &'a str,
 }
 
 #[derive(Template)]
@@ -67,5 +107,37 @@ pub struct CharTemplate<'a> {
     pub variant_of: &'a str,
     pub summary_occurrence_list: &'a str,
     pub word_table: &'a str,
+    pub word_counts: &'a str,
     pub dismantling: &'a str,
 }
+
+#[derive(Template)]
+#[template(path = "cloze.html")]
+pub struct ClozeTemplate<'a> {
+    pub linzklar: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "quiz.html")]
+pub struct QuizTemplate {
+    pub char_count: usize,
+    pub sentence_count: usize,
+    pub audio_count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "dictionary.html")]
+pub struct DictionaryTemplate {
+    pub length: usize,
+}
+
+#[derive(Template)]
+#[template(path = "diagnostics.html")]
+pub struct DiagnosticsTemplate<'a> {
+    pub build_number: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub diagnostics: &'a str,
+    pub failure: &'a str,
+    pub templates_changed: bool,
+}
@@ -0,0 +1,289 @@
+use crate::lint::LintLevels;
+use anyhow::Context as _;
+use serde_derive::Deserialize as De;
+use std::path::{Path, PathBuf};
+
+/// Where the input files are read from.
+#[derive(Debug, Clone)]
+pub struct SourcePaths {
+    /// "Spoonfed Pekzep - SpoonfedPekzep.tsv"
+    pub phrase: PathBuf,
+
+    /// "Spoonfed Pekzep - 語彙整理（超草案）.tsv"
+    pub vocab: PathBuf,
+
+    /// "字音.tsv"
+    pub char_pronunciation: PathBuf,
+
+    /// "contraction.tsv"
+    pub contraction: PathBuf,
+
+    /// "linzklar-dismantling.tsv"
+    pub linzklar_dismantling: PathBuf,
+
+    /// "lint-keywords.tsv"
+    pub lint_keywords: PathBuf,
+
+    /// "lint-suppressions.tsv"
+    pub lint_suppressions: PathBuf,
+
+    /// "lessons.tsv", which is optional
+    pub lessons: PathBuf,
+
+    /// the folder containing the images of the linzklars
+    pub char_img: PathBuf,
+
+    /// the folder searched when an image is not found in `char_img`
+    pub char_img_fallback: PathBuf,
+}
+
+impl SourcePaths {
+    /// Uses the standard file names, all placed directly under `dir`.
+    #[must_use]
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            phrase: dir.join("Spoonfed Pekzep - SpoonfedPekzep.tsv"),
+            vocab: dir.join("Spoonfed Pekzep - 語彙整理（超草案）.tsv"),
+            char_pronunciation: dir.join("字音.tsv"),
+            contraction: dir.join("contraction.tsv"),
+            linzklar_dismantling: dir.join("linzklar-dismantling.tsv"),
+            lint_keywords: dir.join("lint-keywords.tsv"),
+            lint_suppressions: dir.join("lint-suppressions.tsv"),
+            lessons: dir.join("lessons.tsv"),
+            char_img: dir.join("char_img"),
+            char_img_fallback: dir.join("char_img_fallback"),
+        }
+    }
+}
+
+impl Default for SourcePaths {
+    fn default() -> Self {
+        Self::in_dir(Path::new("raw"))
+    }
+}
+
+/// Where the generated files are written to.
+#[derive(Debug, Clone)]
+pub struct OutputPaths {
+    pub docs: PathBuf,
+}
+
+impl OutputPaths {
+    #[must_use]
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            docs: dir.to_owned(),
+        }
+    }
+
+    /// A file placed directly under the output folder, such as `index.html`
+    #[must_use]
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.docs.join(name)
+    }
+
+    #[must_use]
+    pub fn phrase_dir(&self) -> PathBuf {
+        self.docs.join("phrase")
+    }
+
+    #[must_use]
+    pub fn vocab_dir(&self) -> PathBuf {
+        self.docs.join("vocab")
+    }
+
+    #[must_use]
+    pub fn char_dir(&self) -> PathBuf {
+        self.docs.join("char")
+    }
+
+    #[must_use]
+    pub fn lesson_dir(&self) -> PathBuf {
+        self.docs.join("lesson")
+    }
+
+    #[must_use]
+    pub fn char_img_dir(&self) -> PathBuf {
+        self.docs.join("char_img")
+    }
+
+    /// the recordings that are already reviewed
+    #[must_use]
+    pub fn sounds_dir(&self) -> PathBuf {
+        self.docs.join("spoonfed_pekzep_sounds")
+    }
+
+    #[must_use]
+    pub fn nonreviewed_sounds_dir(&self) -> PathBuf {
+        self.docs.join("nonreviewed_sounds")
+    }
+}
+
+impl Default for OutputPaths {
+    fn default() -> Self {
+        Self::in_dir(Path::new("docs"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub source: SourcePaths,
+    pub output: OutputPaths,
+    pub lints: LintLevels,
+    pub graded: GradedSettings,
+}
+
+/// The `[graded]` section of the configuration file
+#[derive(Debug, Clone, De)]
+#[serde(default, deny_unknown_fields)]
+pub struct GradedSettings {
+    /// a phrase introducing more new words than this in the graded order is reported as `too-many-new-words`
+    pub max_new_words: usize,
+}
+
+impl Default for GradedSettings {
+    fn default() -> Self {
+        Self { max_new_words: 2 }
+    }
+}
+
+#[derive(Debug, Default, De)]
+#[serde(default, deny_unknown_fields)]
+struct SourceSection {
+    dir: Option<PathBuf>,
+    phrase: Option<PathBuf>,
+    vocab: Option<PathBuf>,
+    char_pronunciation: Option<PathBuf>,
+    contraction: Option<PathBuf>,
+    linzklar_dismantling: Option<PathBuf>,
+    lint_keywords: Option<PathBuf>,
+    lint_suppressions: Option<PathBuf>,
+    lessons: Option<PathBuf>,
+    char_img: Option<PathBuf>,
+    char_img_fallback: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, De)]
+#[serde(default, deny_unknown_fields)]
+struct OutputSection {
+    dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, De)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    source: SourceSection,
+    output: OutputSection,
+    lints: LintLevels,
+    graded: GradedSettings,
+}
+
+impl Config {
+    /// Parses a TOML configuration of the following form:
+    /// ```toml
+    /// [source]
+    /// dir = "raw"        # every input is looked for in this folder,
+    /// phrase = "a.tsv"   # unless specified individually (relative to `dir`)
+    ///
+    /// [output]
+    /// dir = "docs"
+    ///
+    /// [lints]            # see `LintLevels`
+    /// kan1 = "allow"
+    ///
+    /// [graded]           # see `GradedSettings`
+    /// max_new_words = 2
+    /// ```
+    /// Every key is optional. Relative paths are resolved against `base`, which is usually the folder containing the configuration file.
+    /// # Errors
+    /// Fails if the input is not a valid TOML or contains an unknown key.
+    pub fn from_toml(input: &str, base: &Path) -> anyhow::Result<Self> {
+        let file: ConfigFile = toml::from_str(input)?;
+        let source_dir = base.join(file.source.dir.unwrap_or_else(|| PathBuf::from("raw")));
+        let mut source = SourcePaths::in_dir(&source_dir);
+        let overrides = [
+            (&mut source.phrase, file.source.phrase),
+            (&mut source.vocab, file.source.vocab),
+            (
+                &mut source.char_pronunciation,
+                file.source.char_pronunciation,
+            ),
+            (&mut source.contraction, file.source.contraction),
+            (
+                &mut source.linzklar_dismantling,
+                file.source.linzklar_dismantling,
+            ),
+            (&mut source.lint_keywords, file.source.lint_keywords),
+            (&mut source.lint_suppressions, file.source.lint_suppressions),
+            (&mut source.lessons, file.source.lessons),
+            (&mut source.char_img, file.source.char_img),
+            (&mut source.char_img_fallback, file.source.char_img_fallback),
+        ];
+        for (path, specified) in overrides {
+            if let Some(specified) = specified {
+                *path = source_dir.join(specified);
+            }
+        }
+        Ok(Self {
+            source,
+            output: OutputPaths::in_dir(
+                &base.join(file.output.dir.unwrap_or_else(|| PathBuf::from("docs"))),
+            ),
+            lints: file.lints,
+            graded: file.graded,
+        })
+    }
+
+    /// Reads the configuration file at `path`. Relative paths inside are resolved against the folder containing the file.
+    /// # Errors
+    /// Fails if IO fails or the content is invalid (see [`Config::from_toml`]).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let input = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read the configuration file {}", path.display()))?;
+        Self::from_toml(&input, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::lint::Level;
+    use std::path::Path;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+[source]
+dir = "fixture"
+vocab = "vocab.tsv"
+
+[output]
+dir = "staging"
+
+[lints]
+kan1 = "deny"
+
+[graded]
+max_new_words = 3
+"#,
+            Path::new("base"),
+        )
+        .unwrap();
+        assert_eq!(
+            config.source.phrase,
+            Path::new("base/fixture/Spoonfed Pekzep - SpoonfedPekzep.tsv")
+        );
+        assert_eq!(config.source.vocab, Path::new("base/fixture/vocab.tsv"));
+        assert_eq!(config.output.phrase_dir(), Path::new("base/staging/phrase"));
+        assert_eq!(config.lints.0["kan1"], Level::Deny);
+        assert_eq!(config.graded.max_new_words, 3);
+    }
+
+    #[test]
+    fn test_from_empty_toml() {
+        let config = Config::from_toml("", Path::new("")).unwrap();
+        assert_eq!(config.source.char_img, Path::new("raw/char_img"));
+        assert_eq!(config.output.docs, Path::new("docs"));
+    }
+}
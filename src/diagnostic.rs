@@ -0,0 +1,232 @@
+use serde_derive::Serialize as Ser;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Ser)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// The part of a cell in the tsv that a [`Diagnostic`] points at
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Ser)]
+pub struct Cell {
+    /// the name of the column, such as `pekzep_hanzi`
+    pub field: &'static str,
+
+    /// the whole content of the cell
+    pub text: String,
+
+    /// 1-based, counted in characters
+    pub column: usize,
+
+    /// the number of the characters pointed at
+    pub len: usize,
+}
+
+impl Cell {
+    /// Points at `text[range]`, where `range` is given in bytes.
+    #[must_use]
+    pub fn new(field: &'static str, text: &str, range: std::ops::Range<usize>) -> Self {
+        Self {
+            field,
+            text: text.to_owned(),
+            column: text[..range.start].chars().count() + 1,
+            len: text[range].chars().count(),
+        }
+    }
+
+    /// Points at the whole cell.
+    #[must_use]
+    pub fn whole(field: &'static str, text: &str) -> Self {
+        Self::new(field, text, 0..text.len())
+    }
+}
+
+/// A problem found in the input, along with where it was found
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Ser)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// identifies the check that produced this diagnostic, such as `kan1`
+    pub rule: String,
+
+    pub file: PathBuf,
+
+    /// 1-based; 0 if the diagnostic concerns the file as a whole
+    pub line: usize,
+
+    pub cell: Option<Cell>,
+
+    pub message: String,
+
+    /// how the input should be fixed, if known
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(
+        severity: Severity,
+        rule: &str,
+        file: &Path,
+        line: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity,
+            rule: rule.to_owned(),
+            file: file.to_owned(),
+            line,
+            cell: None,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    pub fn error(rule: &str, file: &Path, line: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, rule, file, line, message)
+    }
+
+    #[must_use]
+    pub fn warning(rule: &str, file: &Path, line: usize, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, rule, file, line, message)
+    }
+
+    #[must_use]
+    pub fn with_cell(mut self, cell: Cell) -> Self {
+        self.cell = Some(cell);
+        self
+    }
+
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    /// Renders in the style of rustc, underlining the offending part of the cell:
+    /// ```
+    /// use spoonfed_pekzep::diagnostic::{Cell, Diagnostic};
+    /// use std::path::Path;
+    /// let diagnostic = Diagnostic::warning("a-before-punctuation", Path::new("a.tsv"), 12, "punctuation after `之`")
+    ///     .with_cell(Cell::new("pekzep_hanzi", "我行之。", 6..9))
+    ///     .with_suggestion("replace it with `噫`");
+    /// assert_eq!(
+    ///     diagnostic.render_human(),
+    ///     "warning[a-before-punctuation]: punctuation after `之`
+    ///   --> a.tsv:12:3 (pekzep_hanzi)
+    ///    |
+    /// 12 | 我行之。
+    ///    |     ^^
+    ///    = help: replace it with `噫`
+    /// "
+    /// );
+    /// ```
+    #[must_use]
+    pub fn render_human(&self) -> String {
+        let mut ans = format!("{}[{}]: {}\n", self.severity, self.rule, self.message);
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        match &self.cell {
+            None if self.line == 0 => {
+                let _ = writeln!(ans, "{gutter}--> {}", self.file.display());
+            }
+            None => {
+                let _ = writeln!(ans, "{gutter}--> {}:{}", self.file.display(), self.line);
+            }
+            Some(cell) => {
+                let _ = writeln!(
+                    ans,
+                    "{gutter}--> {}:{}:{} ({})",
+                    self.file.display(),
+                    self.line,
+                    cell.column,
+                    cell.field
+                );
+                let before = cell.text.chars().take(cell.column - 1).collect::<String>();
+                let pointed = cell
+                    .text
+                    .chars()
+                    .skip(cell.column - 1)
+                    .take(cell.len)
+                    .collect::<String>();
+                let _ = writeln!(ans, "{gutter} |");
+                let _ = writeln!(ans, "{line_number} | {}", cell.text);
+                let _ = writeln!(
+                    ans,
+                    "{gutter} | {}{}",
+                    " ".repeat(before.width()),
+                    "^".repeat(pointed.width().max(1))
+                );
+            }
+        }
+        if let Some(suggestion) = &self.suggestion {
+            let _ = writeln!(ans, "{gutter} = help: {suggestion}");
+        }
+        ans
+    }
+
+    /// Renders as a single-line JSON object.
+    /// # Panics
+    /// Never panics, since every field can be represented in JSON.
+    #[must_use]
+    pub fn render_json(&self) -> String {
+        serde_json::to_string(self).expect("a `Diagnostic` can always be serialized")
+    }
+}
+
+/// Everything found while verifying the input, returned as a single error when at least one of them is an error
+#[derive(Debug, Clone)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    #[must_use]
+    pub fn count(&self, severity: Severity) -> usize {
+        self.0.iter().filter(|d| d.severity == severity).count()
+    }
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{}", diagnostic.render_human())?;
+        }
+        write!(
+            f,
+            "found {} error(s) and {} warning(s) in the input",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cell, Diagnostic};
+    use std::path::Path;
+
+    #[test]
+    fn test_render_json() {
+        let diagnostic = Diagnostic::error("unknown-word", Path::new("a.tsv"), 3, "no such word")
+            .with_cell(Cell::whole("decomposed", "我.心.走"));
+        assert_eq!(
+            diagnostic.render_json(),
+            r#"{"severity":"error","rule":"unknown-word","file":"a.tsv","line":3,"cell":{"field":"decomposed","text":"我.心.走","column":1,"len":5},"message":"no such word","suggestion":null}"#
+        );
+    }
+}
@@ -1,5 +1,14 @@
+pub mod anki;
 pub mod audio_tag;
 pub mod chars;
+pub mod cloze;
+pub mod corpus;
+pub mod dictionary;
+pub mod graded;
+pub mod interlinear;
+pub mod lessons;
 pub mod phrases;
+pub mod quiz;
 pub mod vocab_list_internal;
 pub mod vocabs;
+pub mod worksheets;
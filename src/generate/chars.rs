@@ -1,3 +1,5 @@
+use crate::index::CharUsage;
+use crate::manifest::Build;
 use crate::read::char_pronunciation::Linzklar;
 use crate::read::linzklar_dismantling::{
     self, CustomUnaryOperator, DismantlingTree, IdsBinaryOperator, IdsTrinaryOperator,
@@ -6,12 +8,11 @@ use crate::recurse::{Subtree, Tree, foo3, indent};
 use askama::Template;
 
 use crate::askama_templates::CharTemplate;
+use crate::config::Config;
 use crate::read;
 use crate::{convert_hanzi_to_images, convert_hanzi_to_images_with_size, verify};
+use rayon::prelude::*;
 use std::collections::HashMap;
-use std::error::Error;
-use std::fs::File;
-use std::io::prelude::*;
 
 fn construct_tree_from_dismantlingtree(
     parsed_dismantle: &HashMap<Linzklar, DismantlingTree>,
@@ -100,94 +101,188 @@ fn construct_tree_from_linzklar(
     }
 }
 
-fn get_occurrence_list(data_bundle: &verify::DataBundle, linzklar: Linzklar) -> Vec<String> {
-    let mut occurrence_list = vec![];
-    for verify::Rows3Item {
-        syllables,
-        decomposition: _,
-        row,
-    } in &data_bundle.rows3
-    {
-        if row.pekzep_hanzi.contains(&format!("{linzklar}")) {
-            occurrence_list.push(format!(
-                r#"
+/// How a linzklar is found in the phrases and the words, in English, Chinese and Japanese
+const USAGES: [[&str; 3]; 4] = [
+    [
+        "used as a full syllable",
+        "作为完整音节使用",
+        "単独の音節として使用",
+    ],
+    [
+        "used inside a contraction",
+        "在合音中使用",
+        "合音の中で使用",
+    ],
+    [
+        "used inside the braces of a word",
+        "在词的花括号中使用",
+        "語の波括弧の中で使用",
+    ],
+    [
+        "appears as a variant form",
+        "以异体字出现",
+        "異体字として出現",
+    ],
+];
+
+/// The items containing a linzklar as a full syllable, inside a contraction, inside braces and as a variant form, in the order of [`USAGES`].
+/// Those containing a variant form are listed variant by variant.
+fn by_usage<T: PartialEq>(
+    linzklar: Linzklar,
+    variants: &[&Linzklar],
+    lookup: impl Fn(Linzklar, Option<CharUsage>) -> Vec<T>,
+) -> [Vec<T>; 4] {
+    let mut as_variant = vec![];
+    for item in variants.iter().flat_map(|variant| lookup(**variant, None)) {
+        if !as_variant.contains(&item) {
+            as_variant.push(item);
+        }
+    }
+    [
+        lookup(linzklar, Some(CharUsage::Syllable)),
+        lookup(linzklar, Some(CharUsage::Contraction)),
+        lookup(linzklar, Some(CharUsage::BraceGroup)),
+        as_variant,
+    ]
+}
+
+fn get_occurrence_list(
+    data_bundle: &verify::DataBundle,
+    linzklar: Linzklar,
+    variants: &[&Linzklar],
+) -> String {
+    let index = &data_bundle.index;
+    let occurrences = by_usage(linzklar, variants, |linzklar, usage| {
+        usage
+            .map_or_else(
+                || index.phrases_by_linzklar(linzklar),
+                |usage| index.phrases_by_linzklar_usage(linzklar, usage),
+            )
+            .to_vec()
+    });
+    occurrences
+        .iter()
+        .zip(USAGES)
+        .filter(|(rows, _)| !rows.is_empty())
+        .map(|(rows, [en, zh, ja])| {
+            let list = rows
+                .iter()
+                .map(|&i| {
+                    let verify::Rows3Item { syllables, row, .. } = &data_bundle.rows3[i];
+                    format!(
+                        r#"
             <div style="margin-left: 10px; border-left: 3px solid rgb(34,126,188); padding-left: 5px">
                 <p><span lang="ja">{}</span></p>
                 <p><a href="../phrase/{}.html">{}</a></p>
                 <p><span lang="en">{}</span> / <span lang="zh-CN">{}</span></p>
             </div>"#,
-                row.pekzep_hanzi,
-                read::phrase::syllables_to_str_underscore(syllables),
-                row.pekzep_latin,
-                row.english,
-                row.chinese_hanzi
-            ));
-        }
-    }
-    occurrence_list
+                        row.pekzep_hanzi,
+                        read::phrase::syllables_to_str_underscore(syllables),
+                        row.pekzep_latin,
+                        row.english,
+                        row.chinese_hanzi
+                    )
+                })
+                .collect::<Vec<_>>();
+            let n = rows.len();
+            format!(
+                r#"<details>
+            <summary style="font-size: 80%; font-weight: bold; margin: -0.5em -0.5em 0; padding: 0.5em;"><span lang="en">{en} ({n})</span> / <span lang="zh-CN">{zh}（{n}）</span> / <span lang="ja">{ja}（{n}）</span></summary>
+        {}
+    </details>"#,
+                list.join("\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
+/// The rows of the table of the words containing `linzklar`, together with the counts of the words in the order of [`USAGES`]
 fn get_word_table(
     data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
     linzklar: Linzklar,
+    variants: &[&Linzklar],
     rel_path: &str,
-) -> Vec<String> {
+) -> anyhow::Result<(Vec<String>, [usize; 4])> {
+    use anyhow::Context;
+    let index = &data_bundle.index;
+    let words = by_usage(linzklar, variants, |linzklar, usage| {
+        usage
+            .map_or_else(
+                || index.vocab_by_linzklar(linzklar),
+                |usage| index.vocab_by_linzklar_usage(linzklar, usage),
+            )
+            .to_vec()
+    });
     let mut word_table = vec![];
-    for (key, vocab) in &data_bundle.vocab_ordered {
-        if vocab.pekzep_hanzi.contains(linzklar.as_char()) {
-            let link_path = format!("{rel_path}/vocab/{}.html", key.to_path_safe_string());
-            let rel_path = "..";
-            word_table.push(format!(
-                "<a href=\"{link_path}\">{}</a>\t{}\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{}\t{}\t{}",
-                vocab.pekzep_latin,
-                vocab.pekzep_hanzi,
-                convert_hanzi_to_images(&vocab.pekzep_hanzi, "/{} N()SL«»", rel_path) ,
-                vocab.parts_of_speech,
-                vocab.parts_of_speech_supplement,
-                vocab.english_gloss
-            ));
+    let mut listed = vec![];
+    for key in words.iter().flatten() {
+        if listed.contains(&key) {
+            continue;
         }
+        listed.push(key);
+        let vocab = &data_bundle.vocab_ordered[key];
+        let link_path = format!("{rel_path}/vocab/{}.html", key.to_path_safe_string());
+        let rel_path = "..";
+        word_table.push(format!(
+            "<a href=\"{link_path}\">{}</a>\t{}\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{}\t{}\t{}",
+            vocab.pekzep_latin,
+            vocab.pekzep_hanzi,
+            convert_hanzi_to_images(config, build, &vocab.pekzep_hanzi, "/{} N()SL«»", rel_path)
+                .with_context(|| format!("while rendering the word {key}"))?,
+            vocab.parts_of_speech,
+            vocab.parts_of_speech_supplement,
+            vocab.english_gloss
+        ));
     }
-    word_table
+    Ok((word_table, words.map(|words| words.len())))
 }
 
-/// Generates `char/`
+/// Generates `char/`, rendering the pages in parallel
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn r#gen(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    let parsed_dismantle = linzklar_dismantling::parse()?;
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let parsed_dismantle = linzklar_dismantling::parse(&config.source.linzklar_dismantling)?;
 
     let rel_path = "..";
-    let (char_pronunciation, variants_to_standard) = read::char_pronunciation::parse()?;
+    let (char_pronunciation, variants_to_standard) =
+        read::char_pronunciation::parse(&config.source.char_pronunciation)?;
 
     let extended_char_count = char_pronunciation
         .iter()
         .map(|(lin, _)| (*lin, *data_bundle.char_count.get(lin).unwrap_or(&0)))
         .collect::<HashMap<_, _>>();
 
-    for (linzklar, count) in &extended_char_count {
-        let mut file = File::create(format!("docs/char/{linzklar}.html"))?;
-
+    extended_char_count.par_iter().try_for_each(|(linzklar, count)| -> anyhow::Result<()> {
         let mut variants = variants_to_standard
             .iter()
             .filter_map(|(key, value)| if value == linzklar { Some(key) } else { None })
             .collect::<Vec<_>>();
         variants.sort(); // ソートしておくことで、毎ビルドごとに HTML の差分が出るのを避ける
 
-        let word_table = get_word_table(data_bundle, *linzklar, rel_path);
-        let occurrence_list = get_occurrence_list(data_bundle, *linzklar);
-
-        let summary_occurrence_list = if occurrence_list.is_empty() {
-            String::new()
-        } else {
-            format!(
-                r#"<details>
-            <summary style="font-size: 80%; font-weight: bold; margin: -0.5em -0.5em 0; padding: 0.5em;"><span lang="en">Show all occurrences</span> / <span lang="zh-CN">显示所有例句</span> / <span lang="ja">全ての出現例を表示</span></summary>
-        {}
-    </details>"#,
-                occurrence_list.join("\n")
-            )
-        };
+        let (word_table, word_counts) =
+            get_word_table(data_bundle, config, build, *linzklar, &variants, rel_path)?;
+        let summary_occurrence_list = get_occurrence_list(data_bundle, *linzklar, &variants);
+        // e.g. `used as a full syllable: 3, used inside a contraction: 0, used inside the braces of a word: 0, appears as a variant form: 0`
+        let [en, zh, ja] = [(0, ": ", ", "), (1, "：", "，"), (2, "：", "、")].map(
+            |(lang, colon, separator)| {
+                USAGES
+                    .iter()
+                    .zip(word_counts)
+                    .map(|(usage, n)| format!("{}{colon}{n}", usage[lang]))
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            },
+        );
+        let word_counts = format!(
+            r#"<span lang="en">{en}</span> / <span lang="zh-CN">{zh}</span> / <span lang="ja">{ja}</span>"#
+        );
 
         let variants = if variants.is_empty() {
             String::new()
@@ -200,62 +295,102 @@ pub fn r#gen(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
 </ul>
 </p>"#, variants
             .iter()
-            .map(|variant| format!(
+            .map(|variant| Ok(format!(
                 r#"            <li><span style="filter:brightness(65%) contrast(500%);">{}</span>【{variant}】</li>"#,
-                convert_hanzi_to_images(&format!("{variant}"), "/{} N()SL«»", rel_path)
-            ))
-            .collect::<Vec<_>>()
+                convert_hanzi_to_images(config, build, &format!("{variant}"), "/{} N()SL«»", rel_path)?
+            )))
+            .collect::<anyhow::Result<Vec<_>>>()?
             .join("\n"))
         };
 
-        let variant_of = variants_to_standard.get(linzklar).map_or_else(String::new, |v| {
+        let variant_of = variants_to_standard.get(linzklar).map(|v| -> anyhow::Result<_> {
                 let base_char = format!(
                     r#"<span style="filter:brightness(65%) contrast(500%);">{}</span>【{v}】"#,
-                    convert_hanzi_to_images(&format!("{v}"), "/{} N()SL«»", rel_path)
+                    convert_hanzi_to_images(config, build, &format!("{v}"), "/{} N()SL«»", rel_path)?
                 );
-                format!(
+                Ok(format!(
                     r#"<hr>
     <p><span lang="en">A variant of {base_char}</span> / <span lang="zh-CN">{base_char}的异体字</span> / <span lang="ja">{base_char}の異体字</span></p>"#,
-                )
-            });
+                ))
+            }).transpose()?.unwrap_or_default();
 
         let dismantling = indent(
             4,
             &foo3(construct_tree_from_linzklar(&parsed_dismantle, *linzklar)),
         );
 
-        write!(
-            file,
-            "{}",
+        build.write(
+            &config.output.char_dir().join(format!("{linzklar}.html")),
             CharTemplate {
                 title_img: &format!(
                     "<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>",
                     convert_hanzi_to_images_with_size(
+                        config,
+                        build,
                         &format!("{linzklar}"),
                         "/{} N()SL«»",
                         rel_path,
                         130
-                    ),
+                    )?,
                 ),
                 transcription_char: &format!("{linzklar}"),
                 pronunciations: &char_pronunciation
                     .iter()
-                    .filter_map(|(lin, syl)| if lin == linzklar {
-                        Some(syl.to_string())
-                    } else {
-                        None
+                    .filter_map(|(lin, syl)| {
+                        if lin == linzklar {
+                            Some(syl.to_string())
+                        } else {
+                            None
+                        }
                     })
                     .collect::<Vec<_>>()
                     .join(", "),
                 occurrence_count: &format!("{count}"),
                 word_table: &word_table.join("\n"),
+                word_counts: &word_counts,
                 summary_occurrence_list: &summary_occurrence_list,
                 variants: &variants,
                 variant_of: &variant_of,
                 dismantling: &dismantling,
             }
-            .render()?
+            .render()?,
         )?;
-    }
+        Ok(())
+    })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::by_usage;
+    use crate::index::CharUsage;
+    use crate::read::char_pronunciation::Linzklar;
+
+    #[test]
+    fn items_are_grouped_by_usage() {
+        let linzklar = |c| Linzklar::from_char(c).unwrap();
+        let (standard, variant1, variant2) = (linzklar('言'), linzklar('語'), linzklar('話'));
+        // the phrases 0 to 5, each using one of the linzklars in one way
+        let lookup = |linzklar: Linzklar, usage: Option<CharUsage>| -> Vec<usize> {
+            let uses = [
+                (standard, CharUsage::Syllable),
+                (standard, CharUsage::Contraction),
+                (standard, CharUsage::BraceGroup),
+                (variant1, CharUsage::Syllable),
+                (variant2, CharUsage::Contraction),
+                (variant1, CharUsage::BraceGroup),
+            ];
+            (0..uses.len())
+                .filter(|&i| uses[i].0 == linzklar && usage.is_none_or(|usage| uses[i].1 == usage))
+                .collect()
+        };
+        assert_eq!(
+            by_usage(standard, &[&variant2, &variant1], lookup),
+            [vec![0], vec![1], vec![2], vec![4, 3, 5]]
+        );
+        assert_eq!(
+            by_usage(variant1, &[], lookup),
+            [vec![3], vec![], vec![5], vec![]]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use askama::Template;
+
+use crate::askama_templates::ClozeTemplate;
+use crate::config::Config;
+use crate::convert_hanzi_to_images;
+use crate::generate::interlinear::Interlinear;
+use crate::manifest::Build;
+use crate::read;
+use crate::read::vocab::InternalKey;
+use crate::verify;
+use anyhow::Context;
+use linked_hash_map::LinkedHashMap;
+use serde_derive::Serialize as Ser;
+use std::collections::{BTreeMap, HashMap};
+
+/// How many wrong choices each item offers
+const DISTRACTOR_COUNT: usize = 3;
+
+/// What replaces the blanked word
+const BLANK: &str = "___";
+
+/// A phrase with every occurrence of a word blanked
+#[derive(Debug, Clone, Ser)]
+pub struct ClozeItem {
+    /// the internal key of the blanked word
+    pub word: String,
+
+    /// the phrase, as in `syllables_to_str_underscore`
+    pub phrase: String,
+
+    /// the Latin transcription, one entry per word, with the blanked ones replaced by `___`
+    pub latin: Vec<String>,
+
+    /// the hanzi transcription, one entry per word, with the blanked ones replaced by `___`
+    pub hanzi: Vec<String>,
+
+    /// the English translation, given as a hint
+    pub english: String,
+    pub answer_latin: String,
+    pub answer_hanzi: String,
+
+    /// the Latin transcriptions of the answer and of the distractors, in alphabetical order
+    pub choices: Vec<String>,
+}
+
+/// The words offered as wrong choices for `key`: those of the same part of speech whose frequency is the closest to that of `key`
+fn distractors<'a>(
+    vocab_ordered: &'a LinkedHashMap<InternalKey, read::vocab::Item>,
+    vocab_count: &HashMap<InternalKey, usize>,
+    key: &InternalKey,
+) -> Vec<&'a str> {
+    let voc = &vocab_ordered[key];
+    let count_of = |key| vocab_count.get(key).copied().unwrap_or(0);
+    let mut candidates = vocab_ordered
+        .iter()
+        .filter(|(other_key, other)| {
+            *other_key != key
+                && other.parts_of_speech == voc.parts_of_speech
+                && other.pekzep_latin != voc.pekzep_latin
+                && is_blankable(other)
+        })
+        .map(|(other_key, other)| (count_of(key).abs_diff(count_of(other_key)), other))
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(difference, _)| *difference);
+    let mut ans: Vec<&str> = vec![];
+    for (_, other) in candidates {
+        // two words may share the same Latin transcription
+        if !ans.contains(&other.pekzep_latin.as_str()) {
+            ans.push(&other.pekzep_latin);
+        }
+        if ans.len() == DISTRACTOR_COUNT {
+            break;
+        }
+    }
+    ans
+}
+
+/// A word can be blanked if it is pronounced and is not a splittable compound
+fn is_blankable(voc: &read::vocab::Item) -> bool {
+    !voc.pekzep_latin.is_empty() && !voc.pekzep_latin.contains("//") && voc.pekzep_hanzi != "∅"
+}
+
+/// Makes a [`ClozeItem`] for each pair of a word and a phrase using it, in the order of `vocab_ordered`.
+/// # Errors
+/// Fails if a phrase cannot be glossed, which should have been caught in the verification.
+pub fn items(data_bundle: &verify::DataBundle) -> anyhow::Result<Vec<ClozeItem>> {
+    let mut glossed = vec![];
+    for item in &data_bundle.rows3 {
+        if item.decomposition.is_empty() || item.row.requires_substitution {
+            continue;
+        }
+        let key = read::phrase::syllables_to_str_underscore(&item.syllables);
+        let interlinear =
+            Interlinear::new(item).with_context(|| format!("while glossing the phrase {key}"))?;
+        glossed.push((key, item, interlinear));
+    }
+
+    let mut ans = vec![];
+    for (word, voc) in &data_bundle.vocab_ordered {
+        if !is_blankable(voc) {
+            continue;
+        }
+        let distractors = distractors(&data_bundle.vocab_ordered, &data_bundle.vocab_count, word);
+        if distractors.is_empty() {
+            continue;
+        }
+        for (phrase, item, interlinear) in &glossed {
+            let keys = item.decomposition.iter().flatten().map(|d| &d.key);
+            if !keys.clone().any(|key| key == word) {
+                continue;
+            }
+            let (latin, hanzi) = keys
+                .zip(&interlinear.words)
+                .filter(|(_, glossed)| glossed.latin != "∅")
+                .map(|(key, glossed)| {
+                    if key == word {
+                        (BLANK.to_owned(), BLANK.to_owned())
+                    } else {
+                        (glossed.latin.clone(), glossed.hanzi.clone())
+                    }
+                })
+                .unzip();
+            let mut choices = distractors
+                .iter()
+                .map(|s| (*s).to_owned())
+                .chain(std::iter::once(voc.pekzep_latin.clone()))
+                .collect::<Vec<_>>();
+            choices.sort();
+            ans.push(ClozeItem {
+                word: word.to_string(),
+                phrase: phrase.clone(),
+                latin,
+                hanzi,
+                english: item.row.english.clone(),
+                answer_latin: voc.pekzep_latin.clone(),
+                answer_hanzi: voc.pekzep_hanzi.clone(),
+                choices,
+            });
+        }
+    }
+    Ok(ans)
+}
+
+/// Generates `cloze.json` and `cloze.html`, a quiz page which fetches the items from `cloze.json`
+/// and has the linzklā of every word embedded, rendered as images.
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let items = items(data_bundle)?;
+    build.write(
+        &config.output.file("cloze.json"),
+        serde_json::to_string(&items)?,
+    )?;
+
+    let mut linzklar = BTreeMap::new();
+    for hanzi in items
+        .iter()
+        .flat_map(|item| item.hanzi.iter().chain(std::iter::once(&item.answer_hanzi)))
+    {
+        if hanzi != BLANK && !linzklar.contains_key(hanzi) {
+            let images = convert_hanzi_to_images(config, build, hanzi, "/{} N()SL«»", ".")?;
+            linzklar.insert(hanzi.clone(), images);
+        }
+    }
+
+    // `</script>` inside a string would end the script early
+    let html = ClozeTemplate {
+        linzklar: &serde_json::to_string(&linzklar)?.replace("</", "<\\/"),
+    }
+    .render()?;
+    build.write(&config.output.file("cloze.html"), html)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distractors, is_blankable};
+    use crate::read::vocab::{InternalKey, Item};
+    use linked_hash_map::LinkedHashMap;
+    use std::collections::HashMap;
+
+    /// The words, each with the part of speech and the number of uses
+    fn vocab(
+        words: &[(&str, &str, &str, usize)],
+    ) -> (
+        LinkedHashMap<InternalKey, Item>,
+        HashMap<InternalKey, usize>,
+    ) {
+        let mut vocab_ordered = LinkedHashMap::new();
+        let mut vocab_count = HashMap::new();
+        for (key, latin, parts_of_speech, count) in words {
+            let key = InternalKey::new(key).unwrap();
+            vocab_ordered.insert(key.clone(), Item::for_test(latin, "", parts_of_speech, ""));
+            vocab_count.insert(key, *count);
+        }
+        (vocab_ordered, vocab_count)
+    }
+
+    #[test]
+    fn distractors_are_the_nouns_closest_in_frequency() {
+        let (vocab_ordered, vocab_count) = vocab(&[
+            ("汝", "mua2", "noun", 10),
+            ("我", "pai2", "noun", 9),
+            ("人", "ne2", "noun", 11),
+            ("人2", "ne2", "noun", 12),
+            ("何", "nan2", "noun", 7),
+            ("言", "zep1", "verb", 10),
+            ("汝2", "mua2", "noun", 10),
+            ("享 // 銭", "huep2 // sat1", "noun", 10),
+            ("猫", "mian2", "noun", 20),
+        ]);
+        // 汝2 has the same Latin as the answer, 享 // 銭 cannot be blanked, and ne2 is listed once
+        assert_eq!(
+            distractors(
+                &vocab_ordered,
+                &vocab_count,
+                &InternalKey::new("汝").unwrap()
+            ),
+            ["pai2", "ne2", "nan2"]
+        );
+        // no other verb
+        assert!(
+            distractors(
+                &vocab_ordered,
+                &vocab_count,
+                &InternalKey::new("言").unwrap()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn only_the_pronounced_words_can_be_blanked() {
+        assert!(is_blankable(&Item::for_test("mua2", "汝", "noun", "you")));
+        assert!(!is_blankable(&Item::for_test(
+            "huep2 // sat1",
+            "享 // 銭",
+            "verb",
+            "to pay"
+        )));
+        assert!(!is_blankable(&Item::for_test("", "∅", "particle", "")));
+    }
+}
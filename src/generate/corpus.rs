@@ -0,0 +1,226 @@
+use crate::config::Config;
+use crate::index::Index;
+use crate::manifest::Build;
+use crate::read;
+use crate::read::char_pronunciation::Linzklar;
+use crate::read::vocab::SplittableCompoundInfo;
+use crate::verify;
+use serde_derive::Serialize as Ser;
+use std::collections::HashMap;
+
+/// Bumped whenever a field of `corpus.json` is removed, renamed or changes its meaning.
+/// Adding a field does not bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The content of `corpus.json`
+#[derive(Debug, Ser)]
+pub struct Corpus<'a> {
+    pub schema_version: u32,
+    pub phrases: Vec<Phrase<'a>>,
+    pub vocab: Vec<Word<'a>>,
+    pub chars: Vec<Char>,
+}
+
+#[derive(Debug, Ser)]
+pub struct Phrase<'a> {
+    /// the syllables joined by `_`, such as `mua2_zep1_nan2`, which is also the name of the page in `phrase/`
+    pub key: String,
+    pub english: &'a str,
+    pub japanese: &'a str,
+    pub chinese_pinyin: &'a str,
+    pub chinese_hanzi: &'a str,
+    pub pekzep_latin: &'a str,
+    pub pekzep_hanzi: &'a str,
+
+    /// one list of words per sentence; empty if the phrase is not glossed yet
+    pub decomposition: Vec<Vec<DecomposedWord>>,
+    pub audio: Audio,
+
+    /// who recorded the audio, such as `jekto.vatimeliju`
+    pub author: Option<String>,
+    pub requires_substitution: bool,
+}
+
+#[derive(Debug, Ser)]
+pub struct DecomposedWord {
+    /// the internal key, such as `於dur`, which is also the `key` in `vocab`
+    pub key: String,
+    pub splittable_compound: Option<SplittableCompoundInfo>,
+}
+
+/// Which audio files the spreadsheet says are available
+#[derive(Debug, Ser)]
+pub struct Audio {
+    pub wav: bool,
+    pub oga: bool,
+}
+
+#[derive(Debug, Ser)]
+pub struct Word<'a> {
+    pub key: String,
+    pub pekzep_latin: &'a str,
+    pub pekzep_hanzi: &'a str,
+    pub parts_of_speech: &'a str,
+    pub parts_of_speech_supplement: &'a str,
+    pub english_gloss: &'a str,
+
+    /// how many times the word is used in the phrases
+    pub count: usize,
+}
+
+#[derive(Debug, Ser)]
+pub struct Char {
+    pub linzklar: String,
+
+    /// how many times the character is used in the phrases
+    pub count: usize,
+
+    /// the syllables given in "字音.tsv", such as `zep1`
+    pub readings: Vec<String>,
+
+    /// the contractions in "contraction.tsv" containing the character
+    pub contractions: Vec<Contraction>,
+
+    /// the standard form, if the character is a variant form
+    pub variant_of: Option<String>,
+
+    /// the variant forms, if the character is a standard form
+    pub variants: Vec<String>,
+}
+
+/// A contraction such as `足手`, read as one syllable `xiop1`
+#[derive(Debug, Ser)]
+pub struct Contraction {
+    pub linzklars: String,
+    pub reading: String,
+}
+
+impl<'a> Corpus<'a> {
+    #[must_use]
+    pub fn new(data_bundle: &'a verify::DataBundle) -> Self {
+        use read::phrase::FilePathType;
+        let phrases = data_bundle
+            .rows3
+            .iter()
+            .map(|item| Phrase {
+                key: read::phrase::syllables_to_str_underscore(&item.syllables),
+                english: &item.row.english,
+                japanese: &item.row.japanese,
+                chinese_pinyin: &item.row.chinese_pinyin,
+                chinese_hanzi: &item.row.chinese_hanzi,
+                pekzep_latin: &item.row.pekzep_latin,
+                pekzep_hanzi: &item.row.pekzep_hanzi,
+                decomposition: item
+                    .decomposition
+                    .iter()
+                    .map(|sentence| {
+                        sentence
+                            .iter()
+                            .map(|word| DecomposedWord {
+                                key: word.key.to_string(),
+                                splittable_compound: word.splittable_compound_info,
+                            })
+                            .collect()
+                    })
+                    .collect(),
+                audio: Audio {
+                    wav: item.row.filetype.contains(&FilePathType::Wav),
+                    oga: item.row.filetype.contains(&FilePathType::Oga),
+                },
+                author: item.row.recording_author.as_ref().map(ToString::to_string),
+                requires_substitution: item.row.requires_substitution,
+            })
+            .collect();
+
+        let vocab = data_bundle
+            .vocab_ordered
+            .iter()
+            .map(|(key, voc)| Word {
+                key: key.to_string(),
+                pekzep_latin: &voc.pekzep_latin,
+                pekzep_hanzi: &voc.pekzep_hanzi,
+                parts_of_speech: &voc.parts_of_speech,
+                parts_of_speech_supplement: &voc.parts_of_speech_supplement,
+                english_gloss: &voc.english_gloss,
+                count: data_bundle.vocab_count.get(key).copied().unwrap_or(0),
+            })
+            .collect();
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            phrases,
+            vocab,
+            chars: chars(&data_bundle.char_count, &data_bundle.index),
+        }
+    }
+}
+
+/// The most frequent first, just as in `char_count.js`, with their readings and variants looked up in `index`
+fn chars(char_count: &HashMap<Linzklar, usize>, index: &Index) -> Vec<Char> {
+    let mut char_count: Vec<_> = char_count.iter().collect();
+    char_count.sort_by(|a, b| (b.1, b.0).cmp(&(a.1, a.0)));
+    char_count
+        .into_iter()
+        .map(|(linzklar, count)| Char {
+            linzklar: linzklar.to_string(),
+            count: *count,
+            readings: index
+                .readings(*linzklar)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            contractions: index
+                .contractions(*linzklar)
+                .iter()
+                .map(|(linzklars, reading)| Contraction {
+                    linzklars: linzklars.to_string(),
+                    reading: reading.to_string(),
+                })
+                .collect(),
+            variant_of: index.standard_form(*linzklar).map(|l| l.to_string()),
+            variants: index
+                .variants(*linzklar)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        })
+        .collect()
+}
+
+/// Generates `corpus.json`
+/// # Errors
+/// Will return `Err` if the file I/O fails.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&Corpus::new(data_bundle))?;
+    build.write(&config.output.file("corpus.json"), json + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chars;
+    use crate::index::Index;
+    use crate::read::char_pronunciation::Linzklar;
+    use std::collections::HashMap;
+
+    #[test]
+    fn chars_are_sorted_by_frequency() {
+        let char_count = ['之', '言', '何', '汝']
+            .into_iter()
+            .zip([3, 1, 3, 2])
+            .map(|(c, count)| (Linzklar::from_char(c).unwrap(), count))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            chars(&char_count, &Index::default())
+                .iter()
+                .map(|c| (c.linzklar.as_str(), c.count))
+                .collect::<Vec<_>>(),
+            // the ties are broken by the code point in descending order, as in `char_count.js`
+            [("何", 3), ("之", 3), ("汝", 2), ("言", 1)]
+        );
+    }
+}
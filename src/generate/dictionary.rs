@@ -0,0 +1,251 @@
+use anyhow::Context;
+use askama::Template;
+
+use crate::askama_templates::DictionaryTemplate;
+use crate::config::Config;
+use crate::convert_hanzi_to_images;
+use crate::index::{self, CharUsage};
+use crate::manifest::Build;
+use crate::read::linzklar_dismantling::{self, DismantlingTable};
+use crate::read::vocab::Item;
+use crate::verify;
+use serde_derive::Serialize as Ser;
+use std::collections::BTreeMap;
+
+/// Words too common in the glosses to be looked up by themselves
+const STOP_WORDS: [&str; 6] = ["a", "an", "be", "of", "the", "to"];
+
+/// A word in the dictionary, with the linzklā already rendered as images
+#[derive(Debug, Clone, Ser)]
+pub struct DictionaryEntry {
+    pub key: String,
+    /// the name of the page in `vocab/`
+    pub path: String,
+    pub latin: String,
+    pub hanzi: String,
+    pub images: String,
+    pub parts_of_speech: String,
+    pub english_gloss: String,
+}
+
+/// The words in the order of `vocab_ordered`, together with an index for each field
+/// from a search term to the positions of the words it finds
+#[derive(Debug, Clone, Default, Ser)]
+pub struct Dictionary {
+    pub entries: Vec<DictionaryEntry>,
+    pub english: BTreeMap<String, Vec<usize>>,
+    pub parts_of_speech: BTreeMap<String, Vec<usize>>,
+    pub latin: BTreeMap<String, Vec<usize>>,
+    pub hanzi: BTreeMap<String, Vec<usize>>,
+    pub components: BTreeMap<String, Vec<usize>>,
+}
+
+/// The search terms for an English gloss: each of the glosses separated by commas or semicolons, with the leading "to " stripped,
+/// followed by each of the words in them.
+///
+/// ```
+/// use spoonfed_pekzep::generate::dictionary::english_terms;
+/// assert_eq!(english_terms("to say; to speak"), ["say", "speak"]);
+/// assert_eq!(english_terms("to be white"), ["be white", "white"]);
+/// ```
+#[must_use]
+pub fn english_terms(english_gloss: &str) -> Vec<String> {
+    let mut ans = vec![];
+    let mut words = vec![];
+    for gloss in english_gloss.split([',', ';']) {
+        let gloss = gloss.trim().to_lowercase();
+        let gloss = gloss.strip_prefix("to ").unwrap_or(&gloss);
+        if gloss.is_empty() {
+            continue;
+        }
+        ans.push(gloss.to_owned());
+        words.extend(
+            gloss
+                .split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '-')
+                .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+                .map(ToOwned::to_owned),
+        );
+    }
+    for word in words {
+        if !ans.contains(&word) {
+            ans.push(word);
+        }
+    }
+    ans
+}
+
+fn insert(index: &mut BTreeMap<String, Vec<usize>>, term: String, position: usize) {
+    let positions = index.entry(term).or_default();
+    if positions.last() != Some(&position) {
+        positions.push(position);
+    }
+}
+
+impl Dictionary {
+    /// # Errors
+    /// Fails if "linzklar-dismantling.tsv" cannot be read or a transcription cannot be rendered.
+    pub fn new(
+        data_bundle: &verify::DataBundle,
+        config: &Config,
+        build: &Build,
+    ) -> anyhow::Result<Self> {
+        let table = linzklar_dismantling::parse(&config.source.linzklar_dismantling)?;
+        let mut ans = Self::default();
+        for (position, (key, voc)) in data_bundle.vocab_ordered.iter().enumerate() {
+            ans.add(&table, position, voc)
+                .with_context(|| format!("while indexing the word {key}"))?;
+            ans.entries.push(DictionaryEntry {
+                key: key.to_string(),
+                path: key.to_path_safe_string(),
+                latin: voc.pekzep_latin.clone(),
+                hanzi: voc.pekzep_hanzi.clone(),
+                images: convert_hanzi_to_images(
+                    config,
+                    build,
+                    &voc.pekzep_hanzi,
+                    "/{} N()SL«»",
+                    ".",
+                )?,
+                parts_of_speech: voc.parts_of_speech.clone(),
+                english_gloss: voc.english_gloss.clone(),
+            });
+        }
+        Ok(ans)
+    }
+
+    fn add(&mut self, table: &DismantlingTable, position: usize, voc: &Item) -> anyhow::Result<()> {
+        for term in english_terms(&voc.english_gloss) {
+            insert(&mut self.english, term, position);
+        }
+
+        for pos in [&voc.parts_of_speech, &voc.parts_of_speech_supplement] {
+            let pos = pos.trim().to_lowercase();
+            if !pos.is_empty() {
+                insert(&mut self.parts_of_speech, pos, position);
+            }
+        }
+
+        // `kait kia1` is found by `kait kia1`, `kait` and `kia1`
+        let latin = voc.pekzep_latin.replace("//", " ").to_lowercase();
+        let syllables = latin.split_whitespace().collect::<Vec<_>>();
+        insert(&mut self.latin, syllables.join(" "), position);
+        for syllable in &syllables {
+            insert(&mut self.latin, (*syllable).to_owned(), position);
+        }
+
+        // `与 {於N}` is not found by `於`, which only tells what the word takes
+        insert(&mut self.hanzi, voc.pekzep_hanzi.clone(), position);
+        for (linzklar, usage) in index::linzklar_usages(&voc.pekzep_hanzi)? {
+            if usage == CharUsage::BraceGroup {
+                continue;
+            }
+            insert(&mut self.hanzi, linzklar.to_string(), position);
+            for component in linzklar_dismantling::components(table, linzklar) {
+                insert(&mut self.components, component.to_string(), position);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generates `dictionary.html`, which looks up the words by their English gloss, part of speech, Latin, hanzi or the components of their linzklā,
+/// and its data `dictionary.js`.
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let dictionary = Dictionary::new(data_bundle, config, build)?;
+    build.write(
+        &config.output.file("dictionary.js"),
+        format!(
+            "const DICTIONARY = {};\n",
+            serde_json::to_string(&dictionary)?
+        ),
+    )?;
+    let html = DictionaryTemplate {
+        length: dictionary.entries.len(),
+    }
+    .render()?;
+    build.write(&config.output.file("dictionary.html"), html)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dictionary, english_terms, insert};
+    use crate::read::char_pronunciation::Linzklar;
+    use crate::read::linzklar_dismantling::{DismantlingTable, DismantlingTree};
+    use crate::read::vocab::Item;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn glosses_are_followed_by_their_words() {
+        assert_eq!(
+            english_terms("To Go Out; to leave, exit"),
+            ["go out", "leave", "exit", "go", "out"]
+        );
+    }
+
+    #[test]
+    fn stop_words_are_not_terms_by_themselves() {
+        assert_eq!(
+            english_terms("the end of a road"),
+            ["the end of a road", "end", "road"]
+        );
+        // but "to" in the middle is a stop word as well
+        assert_eq!(
+            english_terms("to want to eat"),
+            ["want to eat", "want", "eat"]
+        );
+    }
+
+    #[test]
+    fn words_are_listed_once_and_keep_apostrophes_and_hyphens() {
+        assert_eq!(
+            english_terms("one's self-esteem, self-esteem; (one's) pride"),
+            [
+                "one's self-esteem",
+                "self-esteem",
+                "(one's) pride",
+                "one's",
+                "pride"
+            ]
+        );
+        assert!(english_terms(" ; ,").is_empty());
+    }
+
+    #[test]
+    fn a_word_is_found_once_per_position() {
+        let mut index = BTreeMap::new();
+        for (term, position) in [("say", 0), ("say", 0), ("speak", 0), ("say", 3)] {
+            insert(&mut index, term.to_owned(), position);
+        }
+        assert_eq!(index["say"], [0, 3]);
+        assert_eq!(index["speak"], [0]);
+    }
+
+    #[test]
+    fn brace_groups_are_not_indexed_as_the_linzklars_of_the_word() {
+        let table = DismantlingTable::from([(
+            Linzklar::from_char('与').unwrap(),
+            DismantlingTree::parse("⿰口人").unwrap(),
+        )]);
+        let mut dictionary = Dictionary::default();
+        for (position, (latin, hanzi)) in [("a // b", "与 {於N}"), ("xiop1", "«足手»")]
+            .into_iter()
+            .enumerate()
+        {
+            let voc = Item::for_test(latin, hanzi, "verb", "to give");
+            dictionary.add(&table, position, &voc).unwrap();
+        }
+        assert_eq!(
+            dictionary.hanzi.keys().collect::<Vec<_>>(),
+            ["«足手»", "与", "与 {於N}", "手", "足"]
+        );
+        assert_eq!(dictionary.components["人"], [0]);
+        assert!(!dictionary.components.contains_key("於"));
+    }
+}
@@ -0,0 +1,68 @@
+use askama::Template;
+
+use crate::askama_templates::GradedIndexTemplate;
+use crate::config::Config;
+use crate::manifest::Build;
+use crate::read;
+use crate::verify;
+
+/// Generates `graded_index.html`, which lists the phrases in the graded order.
+/// # Errors
+/// Will return `Err` if the file I/O or the rendering fails.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let mut index_rows = vec!["step\tnew words\tphrase\tEnglish".to_string()];
+    for (step, graded) in data_bundle.graded().steps.iter().enumerate() {
+        let verify::Rows3Item { syllables, row, .. } = &data_bundle.rows3[graded.index];
+        let key = read::phrase::syllables_to_str_underscore(syllables);
+        let new_words = graded
+            .new_words
+            .iter()
+            .map(|word| {
+                format!(
+                    "<a href=\"vocab/{}.html\">{word}</a>",
+                    word.to_path_safe_string()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        index_rows.push(format!(
+            "{}\t{new_words}\t<a href=\"phrase/{key}.html\">{}</a>\t{}",
+            step + 1,
+            row.pekzep_latin,
+            row.english
+        ));
+    }
+
+    // the phrases without a decomposition follow in the original order
+    for (step, index) in (data_bundle.graded().steps.len()..).zip(&data_bundle.graded().ungraded) {
+        let verify::Rows3Item { syllables, row, .. } = &data_bundle.rows3[*index];
+        index_rows.push(format!(
+            "{}\t(not glossed yet)\t<a href=\"phrase/{}.html\">{}</a>\t{}",
+            step + 1,
+            read::phrase::syllables_to_str_underscore(syllables),
+            row.pekzep_latin,
+            row.english
+        ));
+    }
+
+    build.write(
+        &config.output.file("graded_index.html"),
+        GradedIndexTemplate {
+            index: &index_rows.join("\n"),
+            length: index_rows.len() - 1, /* subtract off the title row */
+            first_link: &data_bundle
+                .graded()
+                .indices()
+                .next()
+                .map_or_else(String::new, |index| {
+                    read::phrase::syllables_to_str_underscore(&data_bundle.rows3[index].syllables)
+                }),
+        }
+        .render()?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,440 @@
+use crate::config::Config;
+use crate::hanzi::{self, HanziToken};
+use crate::manifest::Build;
+use crate::read;
+use crate::verify;
+use std::fmt::Write as _;
+
+/// The third line of an interlinear gloss
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gloss {
+    /// an English word, or English words joined by `.`, such as `say` or `what.kind.of`
+    Lexical(String),
+
+    /// an abbreviation of the grammatical category following the Leipzig Glossing Rules, such as `q` (question marker), shown in small caps
+    Grammatical(String),
+}
+
+/// A lexical gloss longer than this many words is replaced with the abbreviation of the part of speech
+const MAX_GLOSS_WORDS: usize = 4;
+
+/// The abbreviations of the parts of speech and the grammatical categories, taken from the Leipzig Glossing Rules where they have one
+const ABBREVIATIONS: [(&str, &str); 24] = [
+    ("noun", "n"),
+    ("verb", "v"),
+    ("noun-modifier", "adj"),
+    ("verb-modifier", "adv"),
+    ("particle", "prt"),
+    ("post-verbial particle", "prt"),
+    ("sentence-final particle", "prt"),
+    ("post-verbial / sentence-final particle", "prt"),
+    ("conjunction", "conj"),
+    ("interjection", "intj"),
+    ("numeral", "num"),
+    ("quantity unit", "clf"),
+    ("relativizer", "rel"),
+    ("zero-copula", "cop"),
+    ("nominative case marker", "nom"),
+    ("question marker", "q"),
+    ("negative marker", "neg"),
+    ("passive voice marker", "pass"),
+    ("aspect marker", "asp"),
+    ("plural", "pl"),
+    ("plural marker", "pl"),
+    ("imperative-verb-modifier", "imp"),
+    ("politeness", "pol"),
+    ("politeness marker", "pol"),
+];
+
+/// The part of `gloss` before the first `,`, `;`, ` / ` or `. ` that is not inside parentheses or braces
+fn first_sense(gloss: &str) -> &str {
+    let mut depth = 0_usize;
+    for (i, c) in gloss.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth = depth.saturating_sub(1),
+            ',' | ';' if depth == 0 => return &gloss[..i],
+            '/' if depth == 0 && gloss[..i].ends_with(' ') && gloss[i + 1..].starts_with(' ') => {
+                return &gloss[..i];
+            }
+            '.' if depth == 0 && gloss[i + 1..].starts_with(' ') => return &gloss[..i],
+            _ => {}
+        }
+    }
+    gloss
+}
+
+/// Removes the parenthesized notes (even an unclosed one), the optional arguments such as `{to someone}`,
+/// and the ellipses standing for the other words.
+fn strip_notes(gloss: &str) -> String {
+    let mut ans = String::new();
+    let mut depth = 0_usize;
+    for c in gloss.chars() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth = depth.saturating_sub(1),
+            '…' => {}
+            _ if depth == 0 => ans.push(c),
+            _ => {}
+        }
+    }
+    ans.replace("...", " ")
+}
+
+impl Gloss {
+    /// Shortens the English gloss of a word in the Leipzig style.
+    /// Falls back to the abbreviation of the part of speech if the gloss is empty, only explains the usage,
+    /// or is longer than [`MAX_GLOSS_WORDS`] words.
+    #[must_use]
+    pub fn of(voc: &read::vocab::Item) -> Self {
+        Self::new(
+            &voc.english_gloss,
+            &voc.parts_of_speech,
+            &voc.parts_of_speech_supplement,
+        )
+    }
+
+    fn new(english_gloss: &str, parts_of_speech: &str, parts_of_speech_supplement: &str) -> Self {
+        let gloss = first_sense(english_gloss).trim();
+        if let Some(category) = gloss
+            .strip_prefix('(')
+            .and_then(|gloss| gloss.strip_suffix(')'))
+            .filter(|category| !category.contains(char::is_whitespace))
+        {
+            // such as `(plural)`
+            return Self::Grammatical(Self::abbreviate(category, ""));
+        }
+        let gloss = strip_notes(gloss);
+        let gloss = gloss.trim();
+        let gloss = gloss.strip_prefix("to ").unwrap_or(gloss);
+        let words = gloss
+            .split_whitespace()
+            .map(|word| word.trim_end_matches('.'))
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+        if words.is_empty()
+            || words.len() > MAX_GLOSS_WORDS
+            || !gloss.chars().any(char::is_alphanumeric)
+        {
+            Self::Grammatical(Self::abbreviate(
+                parts_of_speech,
+                parts_of_speech_supplement,
+            ))
+        } else {
+            Self::Lexical(words.join("."))
+        }
+    }
+
+    /// `q` for "question marker", `n` for "noun".
+    ///
+    /// The supplement is used when it names a category in [`ABBREVIATIONS`], since it is otherwise a note such as "greeting".
+    /// A part of speech without an abbreviation is written in full, such as `coverb` or `temporal.word`.
+    fn abbreviate(parts_of_speech: &str, parts_of_speech_supplement: &str) -> String {
+        let lookup = |category: &str| {
+            let category = category.trim().to_lowercase();
+            ABBREVIATIONS
+                .iter()
+                .find(|(name, _)| *name == category)
+                .map(|(_, abbreviation)| (*abbreviation).to_owned())
+        };
+        lookup(parts_of_speech_supplement)
+            .or_else(|| lookup(parts_of_speech))
+            .unwrap_or_else(|| {
+                parts_of_speech
+                    .split_whitespace()
+                    .filter(|word| *word != "/")
+                    .collect::<Vec<_>>()
+                    .join(".")
+                    .to_lowercase()
+            })
+    }
+}
+
+/// A word of a phrase, with its latin transcription, hanzi transcription and gloss
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlossedWord {
+    pub latin: String,
+    pub hanzi: String,
+    pub gloss: Gloss,
+}
+
+/// A phrase as an interlinear glossed text: one column per word, followed by the free translation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interlinear {
+    pub words: Vec<GlossedWord>,
+    pub free_translation: String,
+}
+
+impl Interlinear {
+    /// Lines up the words in `row.decomposed` with the syllables of the aligned sentence.
+    /// # Errors
+    /// Fails if a word in the decomposition cannot be tokenized, which should have been caught in the verification.
+    pub fn new(item: &verify::Rows3Item) -> anyhow::Result<Self> {
+        let mut syllables = item.sentence.syllables();
+        let mut words = vec![];
+        for (sentence_gloss, decomposition) in item.row.decomposed.iter().zip(&item.decomposition) {
+            for (key_gloss, decomposition_item) in sentence_gloss.0.iter().zip(decomposition) {
+                let hanzi = key_gloss.to_plaintext();
+                let syllable_count = hanzi::tokenize(&hanzi)?
+                    .iter()
+                    .filter(|token| {
+                        matches!(
+                            token.kind,
+                            HanziToken::Linzklar(_) | HanziToken::Contraction(_) | HanziToken::Xizi
+                        )
+                    })
+                    .count();
+                let latin = syllables
+                    .by_ref()
+                    .take(syllable_count)
+                    .map(|syllable| syllable.latin.as_str())
+                    .collect::<Vec<_>>();
+                words.push(GlossedWord {
+                    latin: if latin.is_empty() {
+                        "∅".to_owned()
+                    } else {
+                        latin.join(" ")
+                    },
+                    hanzi: if hanzi.is_empty() {
+                        "∅".to_owned()
+                    } else {
+                        hanzi
+                    },
+                    gloss: Gloss::of(&decomposition_item.voc),
+                });
+            }
+        }
+        Ok(Self {
+            words,
+            free_translation: item.row.english.clone(),
+        })
+    }
+
+    /// Renders the gloss as HTML, one inline block per word.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let escape = |s: &str| askama::MarkupDisplay::new_unsafe(s, askama::Html).to_string();
+        let mut ans = String::new();
+        for word in &self.words {
+            let gloss = match &word.gloss {
+                Gloss::Lexical(gloss) => escape(gloss),
+                Gloss::Grammatical(abbreviation) => format!(
+                    r#"<span style="font-variant: small-caps;">{}</span>"#,
+                    escape(abbreviation)
+                ),
+            };
+            let _ = write!(
+                ans,
+                r#"<span style="display: inline-block; margin-right: 1em;">{}<br><span lang="ja">{}</span><br>{gloss}</span>"#,
+                escape(&word.latin),
+                escape(&word.hanzi),
+            );
+        }
+        let _ = write!(ans, "<br>‘{}’", escape(&self.free_translation));
+        ans
+    }
+
+    /// Renders the gloss as an example of the LaTeX package `gb4e`, labeled `ex:{label}`.
+    #[must_use]
+    pub fn to_gb4e(&self, label: &str) -> String {
+        let column = |f: &dyn Fn(&GlossedWord) -> String| {
+            self.words
+                .iter()
+                .map(|word| format!("{{{}}}", f(word)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        format!(
+            "\\begin{{exe}}\n\\ex\\label{{ex:{label}}}\n\\glll {}\\\\\n{}\\\\\n{}\\\\\n\\glt `{}'\n\\end{{exe}}\n",
+            column(&|word| escape_latex(&word.latin)),
+            column(&|word| escape_latex(&word.hanzi)),
+            column(&|word| match &word.gloss {
+                Gloss::Lexical(gloss) => escape_latex(gloss),
+                Gloss::Grammatical(abbreviation) => {
+                    format!("\\textsc{{{}}}", escape_latex(abbreviation))
+                }
+            }),
+            escape_latex(&self.free_translation)
+        )
+    }
+}
+
+fn escape_latex(s: &str) -> String {
+    let mut ans = String::new();
+    for c in s.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                ans.push('\\');
+                ans.push(c);
+            }
+            '~' => ans.push_str("\\textasciitilde{}"),
+            '^' => ans.push_str("\\textasciicircum{}"),
+            '\\' => ans.push_str("\\textbackslash{}"),
+            _ => ans.push(c),
+        }
+    }
+    ans
+}
+
+/// Generates `interlinear.tex`, which contains the interlinear gloss of every phrase.
+/// # Errors
+/// Will return `Err` if the file I/O fails.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let mut tex = String::from(
+        "% Generated by spoonfed_pekzep. Requires \\usepackage{gb4e} and a CJK-capable setup such as xeCJK.\n",
+    );
+    for item in &data_bundle.rows3 {
+        if item.row.pekzep_latin.is_empty() || item.decomposition.is_empty() {
+            continue;
+        }
+        tex.push('\n');
+        tex.push_str(
+            &Interlinear::new(item)?
+                .to_gb4e(&read::phrase::syllables_to_str_underscore(&item.syllables)),
+        );
+    }
+    build.write(&config.output.file("interlinear.tex"), tex)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gloss, GlossedWord, Interlinear};
+
+    fn gloss_of(
+        parts_of_speech: &str,
+        parts_of_speech_supplement: &str,
+        english_gloss: &str,
+    ) -> Gloss {
+        Gloss::new(english_gloss, parts_of_speech, parts_of_speech_supplement)
+    }
+
+    fn lexical(s: &str) -> Gloss {
+        Gloss::Lexical(s.to_owned())
+    }
+
+    fn grammatical(s: &str) -> Gloss {
+        Gloss::Grammatical(s.to_owned())
+    }
+
+    #[test]
+    fn gloss_drops_ellipses() {
+        assert_eq!(
+            gloss_of("name suffix", "", "Mr. ..., Ms. ..."),
+            lexical("Mr")
+        );
+        assert_eq!(
+            gloss_of("particle", "nominative case marker", "as for ..., it ..."),
+            lexical("as.for")
+        );
+    }
+
+    #[test]
+    fn gloss_drops_parenthesized_notes() {
+        assert_eq!(
+            gloss_of(
+                "place word",
+                "",
+                "Makati (the capital of the Ai'ren Republic)"
+            ),
+            lexical("Makati")
+        );
+        assert_eq!(gloss_of("name", "", "Ark (name)"), lexical("Ark"));
+        assert_eq!(
+            gloss_of("quantity unit", "", "cubic tswoh (170 ml; 5.75 fl. oz.)"),
+            lexical("cubic.tswoh")
+        );
+    }
+
+    #[test]
+    fn gloss_drops_optional_arguments() {
+        assert_eq!(
+            gloss_of("verb", "", "to give {to someone}"),
+            lexical("give")
+        );
+        assert_eq!(
+            gloss_of("coverb", "", "in {someone's} viewpoint, as for {someone}"),
+            lexical("in.viewpoint")
+        );
+    }
+
+    #[test]
+    fn gloss_takes_the_first_sense() {
+        assert_eq!(gloss_of("noun", "", "thread. string"), lexical("thread"));
+        assert_eq!(gloss_of("verb", "", "to say; to tell"), lexical("say"));
+        assert_eq!(gloss_of("noun", "", "bird / fowl"), lexical("bird"));
+    }
+
+    #[test]
+    fn gloss_keeps_slashes_inside_a_word() {
+        assert_eq!(gloss_of("pronoun", "", "he/she"), lexical("he/she"));
+        assert_eq!(
+            gloss_of("noun", "", "person/people"),
+            lexical("person/people")
+        );
+    }
+
+    #[test]
+    fn gloss_falls_back_to_the_part_of_speech() {
+        assert_eq!(
+            gloss_of("particle", "plural marker", "(plural)"),
+            grammatical("pl")
+        );
+        assert_eq!(
+            gloss_of("particle", "question marker", ""),
+            grammatical("q")
+        );
+        assert_eq!(
+            gloss_of("noun", "", "the ministry in charge of the civil affairs"),
+            grammatical("n")
+        );
+    }
+
+    #[test]
+    fn parts_of_speech_are_abbreviated_as_in_the_leipzig_glossing_rules() {
+        for (parts_of_speech, supplement, abbreviation) in [
+            ("noun", "", "n"),
+            ("Verb", "", "v"),
+            ("noun-modifier", "", "adj"),
+            ("verb-modifier", "intensifier", "adv"),
+            ("sentence-final particle", "", "prt"),
+            ("interjection", "greeting", "intj"),
+            ("verb-modifier", "negative marker", "neg"),
+            ("coverb", "takes a place", "coverb"),
+            ("temporal word", "", "temporal.word"),
+        ] {
+            assert_eq!(
+                Gloss::abbreviate(parts_of_speech, supplement),
+                abbreviation,
+                "{parts_of_speech} ({supplement})"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_gb4e() {
+        let interlinear = Interlinear {
+            words: vec![
+                GlossedWord {
+                    latin: "xizi".to_owned(),
+                    hanzi: "xizi".to_owned(),
+                    gloss: Gloss::Grammatical("ip".to_owned()),
+                },
+                GlossedWord {
+                    latin: "a".to_owned(),
+                    hanzi: "噫".to_owned(),
+                    gloss: Gloss::Lexical("~'s.~".to_owned()),
+                },
+            ],
+            free_translation: "100% sure".to_owned(),
+        };
+        assert_eq!(
+            interlinear.to_gb4e("xizi_a"),
+            "\\begin{exe}\n\\ex\\label{ex:xizi_a}\n\\glll {xizi} {a}\\\\\n{xizi} {噫}\\\\\n{\\textsc{ip}} {\\textasciitilde{}'s.\\textasciitilde{}}\\\\\n\\glt `100\\% sure'\n\\end{exe}\n"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use askama::Template;
+
+use crate::askama_templates::LessonTemplate;
+use crate::config::Config;
+use crate::manifest::Build;
+use crate::read;
+use crate::read::char_pronunciation::Linzklar;
+use crate::sentence::SyllableHanzi;
+use crate::verify;
+use crate::{convert_hanzi_to_images, remove_guillemets};
+use anyhow::Context;
+use linked_hash_map::LinkedHashMap;
+use std::collections::HashSet;
+
+/// The linzklars used in `phrases`, counted in the same way as `char_count`, in the order of their first appearance
+fn char_count_in(rows3: &[verify::Rows3Item], phrases: &[usize]) -> LinkedHashMap<Linzklar, usize> {
+    let mut ans = LinkedHashMap::new();
+    for index in phrases {
+        for syllable in rows3[*index].sentence.syllables() {
+            let linzklars = match &syllable.hanzi {
+                SyllableHanzi::Linzklar(linzklar) => vec![*linzklar],
+                SyllableHanzi::Contraction(contraction) => contraction.0.clone(),
+                SyllableHanzi::Xizi => vec![],
+            };
+            for linzklar in linzklars {
+                *ans.entry(linzklar).or_insert(0_usize) += 1;
+            }
+        }
+    }
+    ans
+}
+
+/// Generates `lesson/`, one page per lesson listing its phrases and the words and the linzklars that no earlier lesson used.
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let lessons = &data_bundle.lessons;
+    let mut seen_words = HashSet::new();
+    let mut seen_chars = HashSet::new();
+    for (i, lesson) in lessons.iter().enumerate() {
+        let number = i + 1;
+        let mut phrases = vec![];
+        let mut new_words = vec![];
+        for index in &lesson.phrases {
+            let verify::Rows3Item {
+                syllables,
+                decomposition,
+                row,
+                ..
+            } = &data_bundle.rows3[*index];
+            phrases.push(format!(
+                "<a href=\"../phrase/{}.html\">{}</a>\t<span lang=\"ja\">{}</span>\t{}",
+                read::phrase::syllables_to_str_underscore(syllables),
+                row.pekzep_latin,
+                remove_guillemets(&row.pekzep_hanzi),
+                row.english
+            ));
+            for item in decomposition.iter().flatten() {
+                if seen_words.insert(&item.key) {
+                    new_words.push(format!(
+                        "<a href=\"../vocab/{}.html\">{}</a>\t{}",
+                        item.key.to_path_safe_string(),
+                        item.key,
+                        item.voc
+                            .to_tab_separated(config, build, "..")
+                            .with_context(|| format!("in the lesson {number}"))?
+                    ));
+                }
+            }
+        }
+
+        let mut new_chars = vec![];
+        for (linzklar, count) in char_count_in(&data_bundle.rows3, &lesson.phrases) {
+            if seen_chars.insert(linzklar) {
+                new_chars.push(format!(
+                    "<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{linzklar}\t{count}",
+                    convert_hanzi_to_images(config, build, &linzklar.to_string(), "", "..")?
+                ));
+            }
+        }
+
+        let link = |n: usize| {
+            if n == 0 || n > lessons.len() {
+                "../index".to_string()
+            } else {
+                n.to_string()
+            }
+        };
+        build.write(
+            &config.output.lesson_dir().join(format!("{number}.html")),
+            LessonTemplate {
+                number,
+                title_en: &lesson.title_en,
+                title_zh: &lesson.title_zh,
+                title_ja: &lesson.title_ja,
+                prev_link: &link(number - 1),
+                next_link: &link(number + 1),
+                phrases: &phrases.join("\n"),
+                new_words: &new_words.join("\n"),
+                new_chars: &new_chars.join("\n"),
+            }
+            .render()?,
+        )?;
+    }
+    Ok(())
+}
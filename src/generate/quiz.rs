@@ -0,0 +1,176 @@
+use askama::Template;
+
+use crate::askama_templates::QuizTemplate;
+use crate::config::Config;
+use crate::manifest::Build;
+use crate::read;
+use crate::read::char_pronunciation::Linzklar;
+use crate::verify;
+use pekzep_syllable::PekZepSyllable;
+use serde_derive::Serialize as Ser;
+
+/// A linzklā together with all its readings in "字音.tsv"
+#[derive(Debug, Clone, Ser)]
+pub struct QuizChar {
+    pub linzklar: String,
+    pub syllables: Vec<String>,
+}
+
+/// A reviewed phrase, with the path to its recording relative to `docs/` if there is one
+#[derive(Debug, Clone, Ser)]
+pub struct QuizSentence {
+    pub key: String,
+    pub english: String,
+    pub latin: String,
+    pub audio: Option<String>,
+}
+
+/// The data used by `quiz.html`, written into `quiz.js`
+#[derive(Debug, Clone, Ser)]
+pub struct QuizData {
+    pub chars: Vec<QuizChar>,
+    pub sentences: Vec<QuizSentence>,
+}
+
+impl QuizData {
+    /// Collects the linzklars whose image is in `config.source.char_img`, in the order of "字音.tsv",
+    /// and the phrases which passed the verification and do not require substitution.
+    /// # Errors
+    /// Fails if "字音.tsv" cannot be read.
+    pub fn new(data_bundle: &verify::DataBundle, config: &Config) -> anyhow::Result<Self> {
+        let (char_pronunciation, _) =
+            read::char_pronunciation::parse(&config.source.char_pronunciation)?;
+        let chars = quiz_chars(&char_pronunciation, |linzklar| has_image(config, linzklar));
+
+        let sentences = data_bundle
+            .rows3
+            .iter()
+            .filter(|item| !item.row.pekzep_latin.is_empty() && !item.row.requires_substitution)
+            .map(|item| {
+                let key = read::phrase::syllables_to_str_underscore(&item.syllables);
+                // only the reviewed recordings; `.oga` is preferred since every browser plays it except Safari
+                let audio = [
+                    ("oga", read::phrase::FilePathType::Oga),
+                    ("wav", read::phrase::FilePathType::Wav),
+                ]
+                .into_iter()
+                .find(|(extension, filetype)| {
+                    item.row.filetype.contains(filetype)
+                        && config
+                            .output
+                            .sounds_dir()
+                            .join(format!("{key}.{extension}"))
+                            .exists()
+                })
+                .map(|(extension, _)| format!("spoonfed_pekzep_sounds/{key}.{extension}"));
+                QuizSentence {
+                    english: item.row.english.clone(),
+                    latin: item.row.pekzep_latin.clone(),
+                    audio,
+                    key,
+                }
+            })
+            .collect();
+
+        Ok(Self { chars, sentences })
+    }
+}
+
+/// Groups the readings in "字音.tsv" by linzklā, in the order each linzklā first appears, leaving out those without an image
+fn quiz_chars(
+    char_pronunciation: &[(Linzklar, PekZepSyllable)],
+    has_image: impl Fn(Linzklar) -> bool,
+) -> Vec<QuizChar> {
+    let mut chars: Vec<(Linzklar, QuizChar)> = vec![];
+    for &(linzklar, syllable) in char_pronunciation {
+        if !has_image(linzklar) {
+            continue;
+        }
+        let syllable = syllable.to_string();
+        if let Some((_, quiz_char)) = chars.iter_mut().find(|(c, _)| *c == linzklar) {
+            if !quiz_char.syllables.contains(&syllable) {
+                quiz_char.syllables.push(syllable);
+            }
+        } else {
+            chars.push((
+                linzklar,
+                QuizChar {
+                    linzklar: linzklar.to_string(),
+                    syllables: vec![syllable],
+                },
+            ));
+        }
+    }
+    chars.into_iter().map(|(_, quiz_char)| quiz_char).collect()
+}
+
+fn has_image(config: &Config, linzklar: Linzklar) -> bool {
+    config
+        .source
+        .char_img
+        .join(format!("{linzklar}.png"))
+        .exists()
+}
+
+/// Generates `quiz.html` and its data `quiz.js`, copying the images of the linzklars used into `char_img/`.
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    let data = QuizData::new(data_bundle, config)?;
+    for quiz_char in &data.chars {
+        let name = format!("{}.png", quiz_char.linzklar);
+        build.copy(
+            &config.source.char_img.join(&name),
+            &config.output.char_img_dir().join(&name),
+        )?;
+    }
+    build.write(
+        &config.output.file("quiz.js"),
+        format!("const QUIZ_DATA = {};\n", serde_json::to_string(&data)?),
+    )?;
+
+    let html = QuizTemplate {
+        char_count: data.chars.len(),
+        sentence_count: data.sentences.len(),
+        audio_count: data
+            .sentences
+            .iter()
+            .filter(|sentence| sentence.audio.is_some())
+            .count(),
+    }
+    .render()?;
+    build.write(&config.output.file("quiz.html"), html)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::quiz_chars;
+    use crate::read::char_pronunciation::Linzklar;
+    use pekzep_syllable::PekZepSyllable;
+
+    #[test]
+    fn readings_are_grouped_by_linzklar() {
+        let linzklar = |c| Linzklar::from_char(c).unwrap();
+        let syllable = |s| PekZepSyllable::parse(s).unwrap();
+        let char_pronunciation = [
+            (linzklar('之'), syllable("a")),
+            (linzklar('言'), syllable("zep1")),
+            (linzklar('噫'), syllable("a")),
+            (linzklar('之'), syllable("mok1")),
+            (linzklar('之'), syllable("a")),
+        ];
+        let chars = quiz_chars(&char_pronunciation, |c| c != linzklar('噫'));
+        assert_eq!(
+            chars
+                .iter()
+                .map(|c| (c.linzklar.as_str(), c.syllables.join(" ")))
+                .collect::<Vec<_>>(),
+            [("之", "a mok1".to_owned()), ("言", "zep1".to_owned())]
+        );
+    }
+}
@@ -2,16 +2,18 @@ use anyhow::anyhow;
 use askama::Template;
 
 use crate::askama_templates::VocabListInternalTemplate;
+use crate::config::Config;
+use crate::manifest::Build;
 use crate::verify;
-use std::error::Error;
-use std::fs::File;
-use std::io::prelude::*;
 
 /// Generates `vocab_list_internal.html`
 /// # Errors
 /// Will return `Err` if the file I/O or the rendering fails.
-pub fn r#gen(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create("docs/vocab_list_internal.html")?;
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
     let mut html = vec![];
     for (key, vocab) in &data_bundle.vocab_ordered {
         let rel_path = ".";
@@ -24,17 +26,16 @@ pub fn r#gen(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
                 .vocab_count
                 .get(key)
                 .ok_or_else(|| anyhow!("vocab_count should be consistent with vocab_ordered"))?,
-            vocab.to_tab_separated(rel_path)
+            vocab.to_tab_separated(config, build, rel_path)?
         ));
     }
-    write!(
-        file,
-        "{}",
+    build.write(
+        &config.output.file("vocab_list_internal.html"),
         VocabListInternalTemplate {
             vocab_html: &html.join("\n"),
             header_row: "internal word id\toccurrence\tPekzep (alphabet)\tPekzep (Chinese characters)\tPekzep (Linzklā)\tparts of speech\tsubdivision\tEnglish translation"
         }
-        .render()?
+        .render()?,
     )?;
     Ok(())
 }
@@ -1,55 +1,70 @@
 use askama::Template;
 
 use crate::askama_templates::VocabTemplate;
+use crate::config::Config;
+use crate::manifest::Build;
 use crate::read;
+use crate::read::vocab::InternalKey;
 use crate::verify;
-use std::error::Error;
+use rayon::prelude::*;
 use std::fmt::Write as _;
-use std::fs::File;
-use std::io::prelude::*;
 
-/// Generates `vocab/`
-/// # Errors
-/// Will return `Err` if the file I/O fails or the render panics.
-pub fn r#gen(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    for (key, v) in &data_bundle.vocab_ordered {
-        let mut file = File::create(format!("docs/vocab/{}.html", key.to_path_safe_string()))?;
+fn gen_page(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+    key: &InternalKey,
+    v: &read::vocab::Item,
+) -> anyhow::Result<()> {
+    let path = config
+        .output
+        .vocab_dir()
+        .join(format!("{}.html", key.to_path_safe_string()));
 
-        let mut usages = String::new();
+    let mut usages = String::new();
 
-        for verify::Rows3Item {
-            syllables,
-            decomposition,
-            row,
-        } in &data_bundle.rows3
-        {
-            if decomposition.iter().flatten().any(|item| item.key == *key) {
-                let _ = write!(
-                    usages,
-                    r#"
+    for &i in data_bundle.index.phrases_by_vocab(key) {
+        let verify::Rows3Item { syllables, row, .. } = &data_bundle.rows3[i];
+        let _ = write!(
+            usages,
+            r#"
             <div style="margin-left: 10px; border-left: 3px solid rgb(34,126,188); padding-left: 5px">
                 <p><span lang="ja">{}</span></p>
                 <p><a href="../phrase/{}.html">{}</a></p>
                 <p><span lang="en">{}</span> / <span lang="zh-CN">{}</span></p>
             </div>"#,
-                    row.pekzep_hanzi,
-                    read::phrase::syllables_to_str_underscore(syllables),
-                    row.pekzep_latin,
-                    row.english,
-                    row.chinese_hanzi
-                );
-            }
+            row.pekzep_hanzi,
+            read::phrase::syllables_to_str_underscore(syllables),
+            row.pekzep_latin,
+            row.english,
+            row.chinese_hanzi
+        );
+    }
+
+    build.write(
+        &path,
+        VocabTemplate {
+            analysis: &v.to_tab_separated(config, build, "..")?,
+            usage_table: &usages,
         }
+        .render()?,
+    )?;
+    Ok(())
+}
 
-        write!(
-            file,
-            "{}",
-            VocabTemplate {
-                analysis: &v.to_tab_separated(".."),
-                usage_table: &usages
-            }
-            .render()?
-        )?;
-    }
+/// Generates `vocab/`, rendering the pages in parallel
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    data_bundle
+        .vocab_ordered
+        .iter()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .try_for_each(|(key, v)| gen_page(data_bundle, config, build, key, v))?;
     Ok(())
 }
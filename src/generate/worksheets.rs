@@ -0,0 +1,266 @@
+use askama::Template;
+
+use crate::askama_templates::WorksheetTemplate;
+use crate::config::Config;
+use crate::generate::interlinear::Interlinear;
+use crate::manifest::Build;
+use crate::read::vocab::InternalKey;
+use crate::verify;
+use crate::{convert_hanzi_to_images, convert_hanzi_to_images_with_size, remove_guillemets};
+use anyhow::Context;
+use std::fmt::Write as _;
+
+/// The exercises made from a set of phrases, each rendered as `<li>` elements, once with blanks and once with the answers
+#[derive(Debug, Clone, Default)]
+pub struct Worksheet {
+    pub translate: String,
+    pub translate_answers: String,
+    pub fill_in: String,
+    pub fill_in_answers: String,
+    pub linzklar: String,
+    pub linzklar_answers: String,
+}
+
+impl Worksheet {
+    /// Makes three exercises from `items`:
+    /// - translating the English (and the Chinese) into Pekzep
+    /// - filling in a word removed from the phrase, which is the least frequent one in the whole corpus; phrases without a decomposition are skipped
+    /// - writing the linzklā under each syllable
+    /// # Errors
+    /// Fails if a transcription cannot be rendered.
+    pub fn new(
+        data_bundle: &verify::DataBundle,
+        config: &Config,
+        build: &Build,
+        items: &[&verify::Rows3Item],
+    ) -> anyhow::Result<Self> {
+        let mut ans = Self::default();
+        for item in items {
+            ans.push(data_bundle, config, build, item)
+                .with_context(|| {
+                    format!("while making the worksheet for {}", item.row.pekzep_latin)
+                })?;
+        }
+        Ok(ans)
+    }
+
+    fn push(
+        &mut self,
+        data_bundle: &verify::DataBundle,
+        config: &Config,
+        build: &Build,
+        item: &verify::Rows3Item,
+    ) -> anyhow::Result<()> {
+        let row = &item.row;
+        let images = convert_hanzi_to_images(
+            config,
+            build,
+            &remove_guillemets(&row.pekzep_hanzi),
+            "() ",
+            "..",
+        )?;
+
+        write!(
+            self.translate,
+            "<li><span lang=\"en\">{}</span><br><span lang=\"zh-CN\">{}</span><div class=\"answer-line\"></div></li>",
+            row.english, row.chinese_hanzi
+        )?;
+        write!(
+            self.translate_answers,
+            "<li><span lang=\"en\">{}</span><br>{}<br><span style=\"filter:brightness(65%) contrast(500%);\">{images}</span></li>",
+            row.english, row.pekzep_latin
+        )?;
+
+        // reported as `undecomposed-lesson-phrase` by `verify`
+        if !item.decomposition.is_empty() {
+            let interlinear = Interlinear::new(item)?;
+            let blank = rarest_word(
+                &interlinear,
+                item.decomposition.iter().flatten().map(|word| &word.key),
+                |key| data_bundle.vocab_count.get(key).copied().unwrap_or(0),
+            );
+            if let Some(blank) = blank {
+                let line = |fill: &dyn Fn(&str) -> String| {
+                    interlinear
+                        .words
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, word)| word.latin != "∅")
+                        .map(|(i, word)| {
+                            if i == blank {
+                                fill(&word.latin)
+                            } else {
+                                word.latin.clone()
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                write!(
+                    self.fill_in,
+                    "<li>{}<br>‘<span lang=\"en\">{}</span>’</li>",
+                    line(&|_| "(__________)".to_owned()),
+                    row.english
+                )?;
+                write!(
+                    self.fill_in_answers,
+                    "<li>{}<br>‘<span lang=\"en\">{}</span>’</li>",
+                    line(&|latin| format!("<u><strong>{latin}</strong></u>")),
+                    row.english
+                )?;
+            }
+        }
+
+        let mut syllables = String::new();
+        let mut boxes = String::new();
+        let mut glyphs = String::new();
+        for syllable in item.sentence.syllables() {
+            write!(syllables, "<td>{}</td>", syllable.latin)?;
+            boxes.push_str("<td class=\"box\"></td>");
+            write!(
+                glyphs,
+                "<td class=\"box\"><span style=\"filter:brightness(65%) contrast(500%);\">{}</span></td>",
+                convert_hanzi_to_images_with_size(
+                    config,
+                    build,
+                    &syllable.hanzi.to_string(),
+                    "() ",
+                    "..",
+                    40
+                )?
+            )?;
+        }
+        write!(
+            self.linzklar,
+            "<li><table class=\"syllables\"><tr>{syllables}</tr><tr>{boxes}</tr></table></li>"
+        )?;
+        write!(
+            self.linzklar_answers,
+            "<li><table class=\"syllables\"><tr>{syllables}</tr><tr>{glyphs}</tr></table></li>"
+        )?;
+        Ok(())
+    }
+}
+
+/// The index of the word to blank out, which is the least frequent one among those pronounced (the first one if tied).
+/// `keys` are the words of `interlinear` in the same order.
+fn rarest_word<'a>(
+    interlinear: &Interlinear,
+    keys: impl Iterator<Item = &'a InternalKey>,
+    frequency: impl Fn(&InternalKey) -> usize,
+) -> Option<usize> {
+    interlinear
+        .words
+        .iter()
+        .zip(keys)
+        .enumerate()
+        .filter(|(_, (word, _))| word.latin != "∅")
+        .min_by_key(|(i, (_, key))| (frequency(key), *i))
+        .map(|(i, _)| i)
+}
+
+/// Generates a worksheet (`lesson/{n}_worksheet.html`) and its answer key (`lesson/{n}_answers.html`) for each lesson.
+/// # Errors
+/// Will return `Err` if the file I/O fails or the render panics.
+pub fn r#gen(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    for (i, lesson) in data_bundle.lessons.iter().enumerate() {
+        let number = i + 1;
+        let items = lesson
+            .phrases
+            .iter()
+            .map(|index| &data_bundle.rows3[*index])
+            .collect::<Vec<_>>();
+        let worksheet = Worksheet::new(data_bundle, config, build, &items)?;
+        let title = format!("{number}. {}", lesson.title_en);
+        let lesson_link = number.to_string();
+        let worksheet_link = format!("{number}_worksheet");
+        let answers_link = format!("{number}_answers");
+        for (link, kind, other_link, other_kind, [translate, fill_in, linzklar]) in [
+            (
+                &worksheet_link,
+                "Worksheet",
+                &answers_link,
+                "Answer key",
+                [
+                    &worksheet.translate,
+                    &worksheet.fill_in,
+                    &worksheet.linzklar,
+                ],
+            ),
+            (
+                &answers_link,
+                "Answer key",
+                &worksheet_link,
+                "Worksheet",
+                [
+                    &worksheet.translate_answers,
+                    &worksheet.fill_in_answers,
+                    &worksheet.linzklar_answers,
+                ],
+            ),
+        ] {
+            let html = WorksheetTemplate {
+                title: &title,
+                kind,
+                lesson_link: &lesson_link,
+                other_link,
+                other_kind,
+                translate,
+                fill_in,
+                linzklar,
+            }
+            .render()?;
+            build.write(
+                &config.output.lesson_dir().join(format!("{link}.html")),
+                html,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rarest_word;
+    use crate::generate::interlinear::{Gloss, GlossedWord, Interlinear};
+    use crate::read::vocab::InternalKey;
+
+    #[test]
+    fn rarest_pronounced_word_is_blanked() {
+        let words = ["xizi", "∅", "mok1", "hia1"];
+        let interlinear = Interlinear {
+            words: words
+                .iter()
+                .map(|latin| GlossedWord {
+                    latin: (*latin).to_owned(),
+                    hanzi: String::new(),
+                    gloss: Gloss::Lexical(String::new()),
+                })
+                .collect(),
+            free_translation: String::new(),
+        };
+        let keys = ["xizi", "∅", "行", "在"].map(|key| InternalKey::new(key).unwrap());
+        let frequency = |counts: [usize; 4]| {
+            let keys = &keys;
+            move |key: &InternalKey| counts[keys.iter().position(|k| k == key).unwrap()]
+        };
+        assert_eq!(
+            rarest_word(&interlinear, keys.iter(), frequency([5, 1, 3, 2])),
+            Some(3)
+        );
+        // the earlier one if tied, and never the unpronounced one
+        assert_eq!(
+            rarest_word(&interlinear, keys.iter(), frequency([5, 1, 2, 2])),
+            Some(2)
+        );
+        // the keys are zipped with the words, so a decomposition shorter than the words is not indexed out of bounds
+        assert_eq!(
+            rarest_word(&interlinear, keys[..1].iter(), frequency([5, 1, 2, 2])),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::read::vocab::InternalKey;
+use crate::verify::Rows3Item;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// A phrase in the graded order, together with the words it is the first to use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradedStep {
+    /// the index in `rows3`
+    pub index: usize,
+
+    /// the words not used by any of the earlier phrases, the most frequent first
+    pub new_words: Vec<InternalKey>,
+}
+
+/// The phrases ordered so that each of them uses as few words not seen before as possible ("i+1" ordering)
+#[derive(Debug, Clone, Default)]
+pub struct GradedOrder {
+    pub steps: Vec<GradedStep>,
+
+    /// the indices of the phrases without a decomposition, which cannot be graded and hence come last in the original order
+    pub ungraded: Vec<usize>,
+}
+
+impl GradedOrder {
+    /// Greedily picks the phrase introducing the fewest new words.
+    /// Among those, the one whose new words are used most often in the whole corpus comes first,
+    /// so that the frequent words are learned early; the remaining ties are broken by the original order.
+    ///
+    /// The phrases are kept in a priority queue keyed by their words not yet seen,
+    /// and only the phrases sharing a word with the one picked are pushed again with their new key,
+    /// so that a step does not go through all the remaining phrases.
+    #[must_use]
+    pub fn new<S: std::hash::BuildHasher>(
+        rows3: &[Rows3Item],
+        vocab_count: &HashMap<InternalKey, usize, S>,
+    ) -> Self {
+        Self::from_words(
+            rows3.iter().map(|item| {
+                item.decomposition
+                    .iter()
+                    .flatten()
+                    .map(|word| &word.key)
+                    .collect()
+            }),
+            |key| vocab_count.get(key).copied().unwrap_or(0),
+        )
+    }
+
+    /// Orders the phrases given by the words they use, an empty set meaning that the phrase has no decomposition.
+    fn from_words<'a>(
+        phrases: impl Iterator<Item = HashSet<&'a InternalKey>>,
+        frequency: impl Fn(&InternalKey) -> usize,
+    ) -> Self {
+        let mut ungraded = vec![];
+        // the words not yet seen, for each index in `rows3`; `None` once the phrase is picked or if it is ungraded
+        let mut unseen: Vec<Option<HashSet<&InternalKey>>> = vec![];
+        // the phrases using each word
+        let mut phrases_by_word: HashMap<&InternalKey, Vec<usize>> = HashMap::new();
+        for (index, words) in phrases.enumerate() {
+            if words.is_empty() {
+                ungraded.push(index);
+                unseen.push(None);
+            } else {
+                for word in &words {
+                    phrases_by_word.entry(word).or_default().push(index);
+                }
+                unseen.push(Some(words));
+            }
+        }
+
+        // the smallest key comes first; an entry whose key is no longer that of its phrase is stale and skipped
+        let key_of = |index: usize, words: &HashSet<&InternalKey>| {
+            let total_frequency = words.iter().map(|key| frequency(key)).sum::<usize>();
+            Reverse((words.len(), Reverse(total_frequency), index))
+        };
+        let mut queue = unseen
+            .iter()
+            .enumerate()
+            .filter_map(|(index, words)| Some(key_of(index, words.as_ref()?)))
+            .collect::<BinaryHeap<_>>();
+
+        let mut steps = vec![];
+        while let Some(Reverse(key @ (_, _, index))) = queue.pop() {
+            let Some(words) = &unseen[index] else {
+                continue;
+            };
+            if key_of(index, words) != Reverse(key) {
+                continue;
+            }
+            let words = unseen[index].take().unwrap_or_default();
+            for word in &words {
+                for &other in &phrases_by_word[word] {
+                    if let Some(other_words) = &mut unseen[other] {
+                        other_words.remove(word);
+                        queue.push(key_of(other, other_words));
+                    }
+                }
+            }
+            let mut new_words = words.into_iter().cloned().collect::<Vec<_>>();
+            new_words.sort_by(|a, b| frequency(b).cmp(&frequency(a)).then_with(|| a.cmp(b)));
+            steps.push(GradedStep { index, new_words });
+        }
+        Self { steps, ungraded }
+    }
+
+    /// The indices in `rows3`, in the graded order
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.steps
+            .iter()
+            .map(|step| step.index)
+            .chain(self.ungraded.iter().copied())
+    }
+
+    /// For each index in `rows3`, the indices of the previous and the next phrase in the graded order
+    #[must_use]
+    pub fn neighbors(&self) -> HashMap<usize, (Option<usize>, Option<usize>)> {
+        let order = self.indices().collect::<Vec<_>>();
+        order
+            .iter()
+            .enumerate()
+            .map(|(i, index)| {
+                let prev = i.checked_sub(1).map(|i| order[i]);
+                (*index, (prev, order.get(i + 1).copied()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GradedOrder;
+    use crate::read::vocab::InternalKey;
+    use std::collections::HashSet;
+
+    fn order(phrases: &[&[&str]], frequent: &[&str]) -> Vec<(usize, Vec<String>)> {
+        let keys = phrases
+            .iter()
+            .map(|words| {
+                words
+                    .iter()
+                    .map(|word| InternalKey::new(word).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let graded = GradedOrder::from_words(
+            keys.iter()
+                .map(|words| words.iter().collect::<HashSet<_>>()),
+            |key| usize::from(frequent.contains(&key.to_string().as_str())),
+        );
+        graded
+            .steps
+            .into_iter()
+            .map(|step| {
+                (
+                    step.index,
+                    step.new_words.iter().map(ToString::to_string).collect(),
+                )
+            })
+            .chain(graded.ungraded.into_iter().map(|index| (index, vec![])))
+            .collect()
+    }
+
+    #[test]
+    fn fewest_new_words_first() {
+        assert_eq!(
+            order(&[&["我", "心", "走"], &["我"], &["我", "心"], &[]], &[]),
+            [
+                (1, vec!["我".to_owned()]),
+                (2, vec!["心".to_owned()]),
+                (0, vec!["走".to_owned()]),
+                (3, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn frequent_words_break_ties() {
+        assert_eq!(
+            order(&[&["我"], &["心"], &["走", "心"]], &["心"]),
+            [
+                (1, vec!["心".to_owned()]),
+                (0, vec!["我".to_owned()]),
+                (2, vec!["走".to_owned()]),
+            ]
+        );
+    }
+}
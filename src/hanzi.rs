@@ -0,0 +1,292 @@
+use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+use std::ops::Range;
+
+/// A meaningful unit found in the hanzi transcription of a phrase or a word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HanziToken {
+    /// a single linzklar, read as one syllable
+    Linzklar(Linzklar),
+
+    /// characters enclosed in guillemets, such as `«足手»`, read together as one syllable
+    Contraction(LinzklarString),
+
+    /// `xizi`
+    Xizi,
+
+    /// `∅`, a word that has no pronunciation
+    Blank,
+
+    /// `。`, `！`, `？`, `「`, `」` or an ASCII punctuation such as `(`
+    Punctuation(char),
+
+    /// a whitespace
+    Space,
+
+    /// the tokens enclosed in braces, such as `{於N}`, which describe what a word takes as an argument
+    BraceGroup(Vec<Token>),
+
+    /// `//`, separating the halves of a splittable compound
+    SplitMarker,
+
+    /// an uppercase ASCII letter such as `N` (a noun) or `S` (a sentence), standing for a slot
+    Placeholder(char),
+}
+
+/// A [`HanziToken`] together with the byte range it was found at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: HanziToken,
+    pub span: Range<usize>,
+}
+
+/// A transcription that cannot be tokenized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    /// the byte range of the offending part
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Splits the hanzi transcription `s` into tokens. Whitespaces are kept as [`HanziToken::Space`].
+/// ```
+/// use spoonfed_pekzep::hanzi::{tokenize, HanziToken};
+/// let tokens = tokenize("xizi 与 {於N}。").unwrap();
+/// assert_eq!(tokens[0].kind, HanziToken::Xizi);
+/// assert_eq!(tokens[0].span, 0..4);
+/// assert_eq!(tokens[1].kind, HanziToken::Space);
+/// assert!(matches!(&tokens[4].kind, HanziToken::BraceGroup(inner) if inner.len() == 2));
+/// assert_eq!(tokens[5].kind, HanziToken::Punctuation('。'));
+/// assert!(tokenize("«足手").is_err());
+/// ```
+/// # Errors
+/// Fails if:
+/// - a `«` or a `{` is not closed
+/// - an `x` is not followed by `izi`
+/// - a character that is neither a linzklar nor listed in [`HanziToken`] is found
+pub fn tokenize(s: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Lexer {
+        s,
+        iter: s.char_indices().peekable(),
+    };
+    lexer.tokens(None)
+}
+
+struct Lexer<'a> {
+    s: &'a str,
+    iter: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Lexer<'_> {
+    fn offset(&mut self) -> usize {
+        self.iter.peek().map_or(self.s.len(), |(i, _)| *i)
+    }
+
+    /// Reads the tokens until `closing` (or the end of the input if `None`) is consumed.
+    fn tokens(&mut self, closing: Option<(char, usize)>) -> Result<Vec<Token>, LexError> {
+        let mut ans = vec![];
+        while let Some((start, c)) = self.iter.next() {
+            if Some(c) == closing.map(|(closing, _)| closing) {
+                return Ok(ans);
+            }
+            let kind = match c {
+                '∅' => HanziToken::Blank,
+                'x' => {
+                    if ['i', 'z', 'i']
+                        .into_iter()
+                        .all(|expected| self.iter.next_if(|(_, c)| *c == expected).is_some())
+                    {
+                        HanziToken::Xizi
+                    } else {
+                        return Err(LexError {
+                            span: start..self.offset(),
+                            message: format!(
+                                "expected `xizi` because `x` was encountered in `{}`, but did not find it",
+                                self.s
+                            ),
+                        });
+                    }
+                }
+                '«' => {
+                    let mut contraction = vec![];
+                    loop {
+                        match self.iter.next() {
+                            Some((_, '»')) => break,
+                            Some((i, c)) => {
+                                contraction.push(Linzklar::from_char(c).map_err(|e| LexError {
+                                    span: i..i + c.len_utf8(),
+                                    message: format!("inside a contraction in `{}`: {e}", self.s),
+                                })?);
+                            }
+                            None => {
+                                return Err(LexError {
+                                    span: start..self.s.len(),
+                                    message: format!(
+                                        "found `«` without the matching `»` in `{}`",
+                                        self.s
+                                    ),
+                                });
+                            }
+                        }
+                    }
+                    HanziToken::Contraction(LinzklarString(contraction))
+                }
+                '{' => {
+                    if closing.is_some() {
+                        return Err(LexError {
+                            span: start..start + 1,
+                            message: format!("braces cannot be nested in `{}`", self.s),
+                        });
+                    }
+                    HanziToken::BraceGroup(self.tokens(Some(('}', start)))?)
+                }
+                '}' | '»' => {
+                    return Err(LexError {
+                        span: start..start + c.len_utf8(),
+                        message: format!("found an unmatched `{c}` in `{}`", self.s),
+                    });
+                }
+                '/' if self.iter.next_if(|(_, c)| *c == '/').is_some() => HanziToken::SplitMarker,
+                'A'..='Z' => HanziToken::Placeholder(c),
+                _ if c.is_whitespace() => HanziToken::Space,
+                _ if c.is_ascii_punctuation() || "。！？「」".contains(c) => {
+                    HanziToken::Punctuation(c)
+                }
+                _ => HanziToken::Linzklar(Linzklar::from_char(c).map_err(|e| LexError {
+                    span: start..start + c.len_utf8(),
+                    message: format!("in `{}`: {e}", self.s),
+                })?),
+            };
+            ans.push(Token {
+                kind,
+                span: start..self.offset(),
+            });
+        }
+        match closing {
+            None => Ok(ans),
+            Some((closing, start)) => Err(LexError {
+                span: start..self.s.len(),
+                message: format!(
+                    "found `{{` without the matching `{closing}` in `{}`",
+                    self.s
+                ),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HanziToken, Token, tokenize};
+    use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+
+    fn linzklar(c: char) -> HanziToken {
+        HanziToken::Linzklar(Linzklar::from_char(c).unwrap())
+    }
+
+    fn token(kind: HanziToken, span: std::ops::Range<usize>) -> Token {
+        Token { kind, span }
+    }
+
+    fn error_span(s: &str) -> std::ops::Range<usize> {
+        tokenize(s).unwrap_err().span
+    }
+
+    #[test]
+    fn contraction() {
+        assert_eq!(
+            tokenize("«足手»在").unwrap(),
+            vec![
+                token(
+                    HanziToken::Contraction(LinzklarString(vec![
+                        Linzklar::from_char('足').unwrap(),
+                        Linzklar::from_char('手').unwrap(),
+                    ])),
+                    0..10
+                ),
+                token(linzklar('在'), 10..13),
+            ]
+        );
+    }
+
+    #[test]
+    fn brace_group() {
+        assert_eq!(
+            tokenize("与 {於N}").unwrap(),
+            vec![
+                token(linzklar('与'), 0..3),
+                token(HanziToken::Space, 3..4),
+                token(
+                    HanziToken::BraceGroup(vec![
+                        token(linzklar('於'), 5..8),
+                        token(HanziToken::Placeholder('N'), 8..9),
+                    ]),
+                    4..10
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn splittable_compound() {
+        assert_eq!(
+            tokenize("享 // 銭").unwrap(),
+            vec![
+                token(linzklar('享'), 0..3),
+                token(HanziToken::Space, 3..4),
+                token(HanziToken::SplitMarker, 4..6),
+                token(HanziToken::Space, 6..7),
+                token(linzklar('銭'), 7..10),
+            ]
+        );
+    }
+
+    #[test]
+    fn xizi_and_blank() {
+        assert_eq!(
+            tokenize("xizi∅噫").unwrap(),
+            vec![
+                token(HanziToken::Xizi, 0..4),
+                token(HanziToken::Blank, 4..7),
+                token(linzklar('噫'), 7..10),
+            ]
+        );
+    }
+
+    #[test]
+    fn parenthesized_annotation() {
+        assert_eq!(
+            tokenize("此(噫)。").unwrap(),
+            vec![
+                token(linzklar('此'), 0..3),
+                token(HanziToken::Punctuation('('), 3..4),
+                token(linzklar('噫'), 4..7),
+                token(HanziToken::Punctuation(')'), 7..8),
+                token(HanziToken::Punctuation('。'), 8..11),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_character() {
+        assert_eq!(error_span("在a"), 3..4);
+        assert_eq!(error_span("«足a»"), 5..6);
+        assert_eq!(error_span("xiz在"), 0..3);
+    }
+
+    #[test]
+    fn unbalanced_brackets() {
+        assert_eq!(error_span("与 {於N"), 4..9);
+        assert_eq!(error_span("与}"), 3..4);
+        assert_eq!(error_span("{於{N}}"), 4..5);
+        assert_eq!(error_span("«足手"), 0..8);
+        assert_eq!(error_span("足手»"), 6..8);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::hanzi::{self, HanziToken};
+use crate::read;
+use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+use crate::read::phrase::Author;
+use crate::read::vocab::{self, InternalKey};
+use crate::verify::Rows3Item;
+use anyhow::Context;
+use linked_hash_map::LinkedHashMap;
+use pekzep_syllable::PekZepSyllable;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// How a linzklar is used in a hanzi transcription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharUsage {
+    /// read as a syllable by itself
+    Syllable,
+
+    /// read together with the other characters of a contraction such as `«足手»` as one syllable
+    Contraction,
+
+    /// inside the braces of a word, such as `於` in `与 {於N}`, telling what the word takes as an argument
+    BraceGroup,
+}
+
+/// The phrases and the words looked up by what they contain.
+///
+/// A phrase is given by its index in `rows3`, and the phrases are listed in the order of `rows3`;
+/// a word is given by its internal key, and the words are listed in the order of `vocab_ordered`.
+/// The readings, the contractions and the variant forms of each linzklar in "字音.tsv" can be looked up as well.
+/// Looking up something that is not in the corpus gives an empty slice.
+///
+/// The linzklars are found by tokenizing the hanzi transcriptions in the same way as `char_count`,
+/// except that those in the braces of a word, such as `於` in `与 {於N}`, are kept as [`CharUsage::BraceGroup`].
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    phrases_by_vocab: HashMap<InternalKey, Vec<usize>>,
+    phrases_by_linzklar: HashMap<Linzklar, Vec<usize>>,
+    phrases_by_linzklar_usage: HashMap<(Linzklar, CharUsage), Vec<usize>>,
+    phrases_by_author: HashMap<Author, Vec<usize>>,
+    vocab_by_linzklar: HashMap<Linzklar, Vec<InternalKey>>,
+    vocab_by_linzklar_usage: HashMap<(Linzklar, CharUsage), Vec<InternalKey>>,
+    vocab_by_part_of_speech: HashMap<String, Vec<InternalKey>>,
+    chars_by_syllable: HashMap<PekZepSyllable, Vec<Linzklar>>,
+    readings_by_linzklar: HashMap<Linzklar, Vec<PekZepSyllable>>,
+    contractions_by_linzklar: HashMap<Linzklar, Vec<(LinzklarString, PekZepSyllable)>>,
+    variants_by_linzklar: HashMap<Linzklar, Vec<Linzklar>>,
+    standard_by_variant: HashMap<Linzklar, Linzklar>,
+}
+
+/// Appends `value` to the list of `key` unless it was the last one appended
+fn push_once<K: Hash + Eq, V: PartialEq>(map: &mut HashMap<K, Vec<V>>, key: K, value: V) {
+    let values = map.entry(key).or_default();
+    if values.last() != Some(&value) {
+        values.push(value);
+    }
+}
+
+/// The linzklars in `hanzi`, each with how it is used
+pub(crate) fn linzklar_usages(hanzi: &str) -> anyhow::Result<Vec<(Linzklar, CharUsage)>> {
+    let mut ans = vec![];
+    for token in hanzi::tokenize(hanzi)? {
+        match token.kind {
+            HanziToken::Linzklar(linzklar) => ans.push((linzklar, CharUsage::Syllable)),
+            HanziToken::Contraction(contraction) => ans.extend(
+                contraction
+                    .0
+                    .into_iter()
+                    .map(|linzklar| (linzklar, CharUsage::Contraction)),
+            ),
+            HanziToken::BraceGroup(tokens) => {
+                for token in tokens {
+                    match token.kind {
+                        HanziToken::Linzklar(linzklar) => {
+                            ans.push((linzklar, CharUsage::BraceGroup));
+                        }
+                        HanziToken::Contraction(contraction) => ans.extend(
+                            contraction
+                                .0
+                                .into_iter()
+                                .map(|linzklar| (linzklar, CharUsage::BraceGroup)),
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(ans)
+}
+
+impl Index {
+    /// Builds the index from the verified phrases, the words they use,
+    /// the readings and the variants in "字音.tsv" and the contractions in "contraction.tsv".
+    /// # Errors
+    /// Fails if a hanzi transcription cannot be tokenized, which should have been caught in the verification.
+    pub fn new(
+        rows3: &[Rows3Item],
+        vocab_ordered: &LinkedHashMap<InternalKey, vocab::Item>,
+        char_pronunciation: &[(Linzklar, PekZepSyllable)],
+        contraction_pronunciation: &[(LinzklarString, PekZepSyllable)],
+        variants_to_standard: &HashMap<Linzklar, Linzklar>,
+    ) -> anyhow::Result<Self> {
+        let mut index = Self::default();
+        for (i, item) in rows3.iter().enumerate() {
+            index
+                .add_phrase(
+                    i,
+                    item.decomposition
+                        .iter()
+                        .flatten()
+                        .map(|decomposition_item| &decomposition_item.key),
+                    &item.row.pekzep_hanzi,
+                    item.row.recording_author.as_ref(),
+                )
+                .with_context(|| {
+                    format!(
+                        "while indexing the phrase {}",
+                        read::phrase::syllables_to_str_underscore(&item.syllables)
+                    )
+                })?;
+        }
+        for (key, item) in vocab_ordered {
+            index
+                .add_word(key, &item.pekzep_hanzi, &item.parts_of_speech)
+                .with_context(|| format!("while indexing the word {key}"))?;
+        }
+        for (linzklar, syllable) in char_pronunciation {
+            index.add_reading(*linzklar, *syllable);
+        }
+        for (contraction, syllable) in contraction_pronunciation {
+            index.add_contraction(contraction, *syllable);
+        }
+        for (variant, standard) in variants_to_standard {
+            index.add_variant(*variant, *standard);
+        }
+        Ok(index)
+    }
+
+    /// Indexes the `i`-th phrase, which uses the words `keys` and is transcribed as `hanzi`
+    fn add_phrase<'a>(
+        &mut self,
+        i: usize,
+        keys: impl Iterator<Item = &'a InternalKey>,
+        hanzi: &str,
+        author: Option<&Author>,
+    ) -> anyhow::Result<()> {
+        for key in keys {
+            push_once(&mut self.phrases_by_vocab, key.clone(), i);
+        }
+        for (linzklar, usage) in linzklar_usages(hanzi)? {
+            push_once(&mut self.phrases_by_linzklar, linzklar, i);
+            push_once(&mut self.phrases_by_linzklar_usage, (linzklar, usage), i);
+        }
+        if let Some(author) = author {
+            push_once(&mut self.phrases_by_author, author.clone(), i);
+        }
+        Ok(())
+    }
+
+    /// Indexes the word `key`, which is transcribed as `hanzi`
+    fn add_word(
+        &mut self,
+        key: &InternalKey,
+        hanzi: &str,
+        part_of_speech: &str,
+    ) -> anyhow::Result<()> {
+        for (linzklar, usage) in linzklar_usages(hanzi)? {
+            push_once(&mut self.vocab_by_linzklar, linzklar, key.clone());
+            push_once(
+                &mut self.vocab_by_linzklar_usage,
+                (linzklar, usage),
+                key.clone(),
+            );
+        }
+        push_once(
+            &mut self.vocab_by_part_of_speech,
+            part_of_speech.to_owned(),
+            key.clone(),
+        );
+        Ok(())
+    }
+
+    /// Indexes a reading in "字音.tsv"; a linzklar listed twice under the same syllable is kept once
+    fn add_reading(&mut self, linzklar: Linzklar, syllable: PekZepSyllable) {
+        let chars = self.chars_by_syllable.entry(syllable).or_default();
+        if !chars.contains(&linzklar) {
+            chars.push(linzklar);
+        }
+        let readings = self.readings_by_linzklar.entry(linzklar).or_default();
+        if !readings.contains(&syllable) {
+            readings.push(syllable);
+        }
+    }
+
+    /// Indexes a contraction in "contraction.tsv" under each of the linzklars it contains
+    fn add_contraction(&mut self, contraction: &LinzklarString, syllable: PekZepSyllable) {
+        for linzklar in &contraction.0 {
+            let contractions = self.contractions_by_linzklar.entry(*linzklar).or_default();
+            if !contractions.iter().any(|(c, _)| c == contraction) {
+                contractions.push((contraction.clone(), syllable));
+            }
+        }
+    }
+
+    /// Indexes `variant` as a variant form of `standard`, keeping the variants of `standard` sorted
+    fn add_variant(&mut self, variant: Linzklar, standard: Linzklar) {
+        self.standard_by_variant.insert(variant, standard);
+        let variants = self.variants_by_linzklar.entry(standard).or_default();
+        if let Err(i) = variants.binary_search(&variant) {
+            variants.insert(i, variant);
+        }
+    }
+
+    /// The phrases whose decomposition uses the word `key`
+    #[must_use]
+    pub fn phrases_by_vocab(&self, key: &InternalKey) -> &[usize] {
+        self.phrases_by_vocab.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// The phrases whose hanzi transcription contains `linzklar`, used in any way
+    #[must_use]
+    pub fn phrases_by_linzklar(&self, linzklar: Linzklar) -> &[usize] {
+        self.phrases_by_linzklar
+            .get(&linzklar)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The phrases whose hanzi transcription contains `linzklar` used as `usage`
+    #[must_use]
+    pub fn phrases_by_linzklar_usage(&self, linzklar: Linzklar, usage: CharUsage) -> &[usize] {
+        self.phrases_by_linzklar_usage
+            .get(&(linzklar, usage))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The phrases recorded by `author`
+    #[must_use]
+    pub fn phrases_by_author(&self, author: &Author) -> &[usize] {
+        self.phrases_by_author
+            .get(author)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The words whose hanzi transcription contains `linzklar`, used in any way
+    #[must_use]
+    pub fn vocab_by_linzklar(&self, linzklar: Linzklar) -> &[InternalKey] {
+        self.vocab_by_linzklar
+            .get(&linzklar)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The words whose hanzi transcription contains `linzklar` used as `usage`
+    #[must_use]
+    pub fn vocab_by_linzklar_usage(&self, linzklar: Linzklar, usage: CharUsage) -> &[InternalKey] {
+        self.vocab_by_linzklar_usage
+            .get(&(linzklar, usage))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The words whose part of speech is exactly `part_of_speech`, such as `"noun"` or `"verb-object compound"`
+    #[must_use]
+    pub fn vocab_by_part_of_speech(&self, part_of_speech: &str) -> &[InternalKey] {
+        self.vocab_by_part_of_speech
+            .get(part_of_speech)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The linzklars read as `syllable`, in the order of "字音.tsv"
+    #[must_use]
+    pub fn chars_by_syllable(&self, syllable: PekZepSyllable) -> &[Linzklar] {
+        self.chars_by_syllable
+            .get(&syllable)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The syllables `linzklar` is read as, in the order of "字音.tsv"
+    #[must_use]
+    pub fn readings(&self, linzklar: Linzklar) -> &[PekZepSyllable] {
+        self.readings_by_linzklar
+            .get(&linzklar)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The contractions containing `linzklar`, each with its reading, in the order of "contraction.tsv"
+    #[must_use]
+    pub fn contractions(&self, linzklar: Linzklar) -> &[(LinzklarString, PekZepSyllable)] {
+        self.contractions_by_linzklar
+            .get(&linzklar)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The variant forms of `linzklar`, sorted
+    #[must_use]
+    pub fn variants(&self, linzklar: Linzklar) -> &[Linzklar] {
+        self.variants_by_linzklar
+            .get(&linzklar)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The standard form of `linzklar`, if it is a variant form
+    #[must_use]
+    pub fn standard_form(&self, linzklar: Linzklar) -> Option<Linzklar> {
+        self.standard_by_variant.get(&linzklar).copied()
+    }
+
+    /// The parts of speech of the words used in the corpus, sorted
+    #[must_use]
+    pub fn parts_of_speech(&self) -> Vec<&str> {
+        let mut ans = self
+            .vocab_by_part_of_speech
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        ans.sort_unstable();
+        ans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CharUsage, Index, linzklar_usages};
+    use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+    use crate::read::phrase::Author;
+    use crate::read::vocab::InternalKey;
+    use pekzep_syllable::PekZepSyllable;
+
+    fn linzklar(c: char) -> Linzklar {
+        Linzklar::from_char(c).unwrap()
+    }
+
+    fn key(s: &str) -> InternalKey {
+        InternalKey::new(s).unwrap()
+    }
+
+    #[test]
+    fn usages_are_told_apart() {
+        assert_eq!(
+            linzklar_usages("xizi «足手» 与 {於N}。").unwrap(),
+            [
+                (linzklar('足'), CharUsage::Contraction),
+                (linzklar('手'), CharUsage::Contraction),
+                (linzklar('与'), CharUsage::Syllable),
+                (linzklar('於'), CharUsage::BraceGroup),
+            ]
+        );
+    }
+
+    #[test]
+    fn phrases_are_looked_up_in_order_and_listed_once() {
+        let (yan, he) = (key("言"), key("何"));
+        let mut index = Index::default();
+        index
+            .add_phrase(0, [&yan, &yan].into_iter(), "言言。", None)
+            .unwrap();
+        index
+            .add_phrase(
+                1,
+                [&he, &yan].into_iter(),
+                "何言。",
+                Some(&Author::JektoVatimeliju),
+            )
+            .unwrap();
+        assert_eq!(index.phrases_by_vocab(&yan), [0, 1]);
+        assert_eq!(index.phrases_by_vocab(&he), [1]);
+        assert_eq!(index.phrases_by_linzklar(linzklar('言')), [0, 1]);
+        assert_eq!(
+            index.phrases_by_linzklar_usage(linzklar('何'), CharUsage::Syllable),
+            [1]
+        );
+        assert_eq!(index.phrases_by_author(&Author::JektoVatimeliju), [1]);
+    }
+
+    #[test]
+    fn unknown_keys_give_empty_slices() {
+        let mut index = Index::default();
+        index.add_word(&key("言"), "言", "verb").unwrap();
+        assert!(index.phrases_by_vocab(&key("言")).is_empty());
+        assert!(index.phrases_by_linzklar(linzklar('言')).is_empty());
+        assert!(index.vocab_by_linzklar(linzklar('何')).is_empty());
+        assert!(
+            index
+                .vocab_by_linzklar_usage(linzklar('言'), CharUsage::Contraction)
+                .is_empty()
+        );
+        assert!(index.vocab_by_part_of_speech("noun").is_empty());
+        assert!(index.phrases_by_author(&Author::FaliraLyjotafis).is_empty());
+        assert!(
+            index
+                .chars_by_syllable(PekZepSyllable::parse("a").unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn words_are_looked_up_by_linzklar_and_part_of_speech() {
+        let mut index = Index::default();
+        index.add_word(&key("«足手»"), "«足手»", "noun").unwrap();
+        index.add_word(&key("言"), "言", "verb").unwrap();
+        index.add_word(&key("与"), "与 {於N}", "verb").unwrap();
+        assert_eq!(index.vocab_by_linzklar(linzklar('手')), [key("«足手»")]);
+        assert_eq!(
+            index.vocab_by_linzklar_usage(linzklar('於'), CharUsage::BraceGroup),
+            [key("与")]
+        );
+        assert_eq!(
+            index.vocab_by_part_of_speech("verb"),
+            [key("言"), key("与")]
+        );
+        assert_eq!(index.parts_of_speech(), ["noun", "verb"]);
+    }
+
+    #[test]
+    fn readings_are_listed_in_order_and_once() {
+        let a = PekZepSyllable::parse("a").unwrap();
+        let mut index = Index::default();
+        for c in ['噫', '之', '噫'] {
+            index.add_reading(linzklar(c), a);
+        }
+        assert_eq!(index.chars_by_syllable(a), [linzklar('噫'), linzklar('之')]);
+        assert_eq!(index.readings(linzklar('噫')), [a]);
+    }
+
+    #[test]
+    fn contractions_and_variants_are_looked_up_by_linzklar() {
+        let xiop1 = PekZepSyllable::parse("xiop1").unwrap();
+        let contraction = LinzklarString::new("足手").unwrap();
+        let mut index = Index::default();
+        index.add_contraction(&contraction, xiop1);
+        for variant in ['語', '話'] {
+            index.add_variant(linzklar(variant), linzklar('言'));
+        }
+        assert_eq!(index.contractions(linzklar('手')), [(contraction, xiop1)]);
+        assert!(index.contractions(linzklar('言')).is_empty());
+        assert_eq!(
+            index.variants(linzklar('言')),
+            [linzklar('話'), linzklar('語')]
+        );
+        assert_eq!(index.standard_form(linzklar('話')), Some(linzklar('言')));
+        assert_eq!(index.standard_form(linzklar('言')), None);
+    }
+}
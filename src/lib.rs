@@ -4,19 +4,48 @@ use askama::Template;
 use read::char_pronunciation::Linzklar;
 
 use crate::askama_templates::{CharListTemplate, IndTemplate, VocabListTemplate};
+use crate::config::Config;
+use crate::manifest::Build;
 use crate::read::vocab::SplittableCompoundInfo;
 use std::collections::HashMap;
-use std::error::Error;
 use std::fmt::Write as _;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
+
+/// where to read the input files from and where to write the output to
+pub mod config;
 
 /// reads from the input files
 pub mod read;
 
+/// splits the hanzi transcriptions of the phrases and the words into tokens
+pub mod hanzi;
+
+/// a phrase with its hanzi transcription, syllables and latin transcription aligned
+pub mod sentence;
+
+/// problems found in the input, with the file, the line and the cell they were found in
+pub mod diagnostic;
+
+/// the checks that only produce warnings, and their configuration
+pub mod lint;
+
 /// checks whether all the data collected from the input files are consistent with each other
 pub mod verify;
 
+/// orders the phrases so that each of them introduces as few new words as possible
+pub mod graded;
+
+/// looks up the phrases and the words by the words, the linzklars, the parts of speech, the recording authors and the syllables
+pub mod index;
+
+/// writes the output only when its content has changed since the previous build
+pub mod manifest;
+
+/// a local preview server, which rebuilds the website when the input changes
+pub mod serve;
+
 /// Pure functions that are used to normalize the input
 pub mod normalizer;
 
@@ -27,42 +56,65 @@ pub mod generate;
 
 mod recurse;
 
+/// The 64-bit FNV-1a hash, which, unlike `DefaultHasher`, is guaranteed not to change between Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
 /// Splits the string at the first occurrence of `//`.
-/// # Panic
-/// Panics if the string does not have a `//`.
-fn split_at_slashslash(in_string: &str) -> (String, String) {
-    let (first, second) = in_string.split_once("//").unwrap();
-    (first.to_owned(), second.to_owned())
+/// # Errors
+/// Fails if the string does not have a `//`.
+fn split_at_slashslash(in_string: &str) -> anyhow::Result<(String, String)> {
+    let (first, second) = in_string
+        .split_once("//")
+        .ok_or_else(|| anyhow::anyhow!("expected `//` in `{in_string}`, but did not find it"))?;
+    Ok((first.to_owned(), second.to_owned()))
 }
 
 impl read::vocab::Item {
-    #[must_use]
-    pub fn to_tab_separated(&self, rel_path: &'static str) -> String {
+    /// # Errors
+    /// Fails if `pekzep_hanzi` cannot be converted into images.
+    pub fn to_tab_separated(
+        &self,
+        config: &Config,
+        build: &Build,
+        rel_path: &'static str,
+    ) -> anyhow::Result<String> {
         self.to_tab_separated_with_custom_linzifier(|s| {
-            convert_hanzi_to_images(s, "/{} N()SL«»", rel_path)
+            convert_hanzi_to_images(config, build, s, "/{} N()SL«»", rel_path)
         })
     }
 }
 impl verify::DecompositionItem {
-    #[must_use]
+    /// # Errors
+    /// Fails if `pekzep_hanzi` cannot be converted into images,
+    /// or if a splittable compound does not have `//` in it.
     pub fn to_tab_separated_with_splittable_compound_info_and_also_with_a_link(
         &self,
+        config: &Config,
+        build: &Build,
         rel_path: &'static str,
-    ) -> String {
+    ) -> anyhow::Result<String> {
+        use anyhow::Context;
         let link_path = format!("{rel_path}/vocab/{}.html", self.key.to_path_safe_string());
-        self.splittable_compound_info.map_or_else(|| format!(
+        self.splittable_compound_info.map_or_else(|| anyhow::Ok(format!(
                 "<a href=\"{}\">{}</a>\t{}\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{}\t{}\t{}",
                 link_path,
                 self.voc.pekzep_latin,
                 self.voc.pekzep_hanzi,
-                convert_hanzi_to_images(&self.voc.pekzep_hanzi,  "/{} N()SL«»", rel_path),
+                convert_hanzi_to_images(config, build, &self.voc.pekzep_hanzi,  "/{} N()SL«»", rel_path)?,
                 self.voc.parts_of_speech,
                 self.voc.parts_of_speech_supplement,
                 self.voc.english_gloss
-            ), |splittable| {
-            let (latin_former, latin_latter) = split_at_slashslash(&self.voc.pekzep_latin);
-            let (hanzi_former, hanzi_latter) = split_at_slashslash(&self.voc.pekzep_hanzi);
-            match splittable {
+            )), |splittable| {
+            let (latin_former, latin_latter) = split_at_slashslash(&self.voc.pekzep_latin)?;
+            let (hanzi_former, hanzi_latter) = split_at_slashslash(&self.voc.pekzep_hanzi)?;
+            Ok(match splittable {
                 SplittableCompoundInfo::FormerHalfHash => {
                     format!(
                         "<a href=\"{}\">{}<span style=\"font-size: 75%; color: #444\">//{}</span></a>\t{}<span style=\"font-size: 75%; color: #444\">//{}</span>\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>//<span style=\"filter:brightness(80%) contrast(80%);\">{}</span>\t{}\t{}\t{}",
@@ -71,8 +123,8 @@ impl verify::DecompositionItem {
                         latin_latter,
                         hanzi_former,
                         hanzi_latter,
-                        &convert_hanzi_to_images_with_size(&hanzi_former, "/{} N()SL«»", rel_path, 30),
-                        &convert_hanzi_to_images_with_size(&hanzi_latter, "/{} N()SL«»", rel_path, 22),
+                        &convert_hanzi_to_images_with_size(config, build, &hanzi_former, "/{} N()SL«»", rel_path, 30)?,
+                        &convert_hanzi_to_images_with_size(config, build, &hanzi_latter, "/{} N()SL«»", rel_path, 22)?,
                         self.voc.parts_of_speech,
                         self.voc.parts_of_speech_supplement,
                         self.voc.english_gloss
@@ -86,15 +138,16 @@ impl verify::DecompositionItem {
                         latin_latter,
                         hanzi_former,
                         hanzi_latter,
-                        &convert_hanzi_to_images_with_size(&hanzi_former, "/{} N()SL«»", rel_path, 22),
-                        &convert_hanzi_to_images_with_size(&hanzi_latter, "/{} N()SL«»", rel_path, 30),
+                        &convert_hanzi_to_images_with_size(config, build, &hanzi_former, "/{} N()SL«»", rel_path, 22)?,
+                        &convert_hanzi_to_images_with_size(config, build, &hanzi_latter, "/{} N()SL«»", rel_path, 30)?,
                         self.voc.parts_of_speech,
                         self.voc.parts_of_speech_supplement,
                         self.voc.english_gloss
                     )
                 }
-            }
+            })
         })
+        .with_context(|| format!("while rendering the word {}", self.key))
     }
 }
 
@@ -117,94 +170,270 @@ const fn to_check_or_parencheck(a: R) -> &'static str {
     }
 }
 
-fn char_img_with_size(name: &str, rel_path: &'static str, size: usize, gen_link: bool) -> String {
+fn char_img_with_size(
+    config: &Config,
+    build: &Build,
+    name: &str,
+    rel_path: &'static str,
+    size: usize,
+    gen_link: bool,
+) -> std::io::Result<String> {
     use log::info;
-    if std::path::Path::new(&format!("raw/char_img/{name}.png")).exists() {
+    let source = config.source.char_img.join(format!("{name}.png"));
+    let fallback = config.source.char_img_fallback.join(format!("{name}.png"));
+    let output_dir = config.output.char_img_dir();
+    if source.exists() {
         // only copy the files that are actually used
-        match std::fs::copy(
-            format!("raw/char_img/{name}.png"),
-            format!("docs/char_img/{name}.png"),
-        ) {
-            Ok(_) => {}
+        match build.copy(&source, &output_dir.join(format!("{name}.png"))) {
+            Ok(()) => {}
             Err(e) => {
-                eprintln!("Copying raw/char_img/{name}.png failed: {e}");
+                eprintln!("Copying {} failed: {e}", source.display());
             }
         }
-    } else if std::path::Path::new(&format!("raw/char_img_fallback/{name}.png")).exists() {
-        match std::fs::copy(
-            format!("raw/char_img_fallback/{name}.png"),
-            format!("docs/char_img/{name}.png"),
-        ) {
-            Ok(_) => {
+    } else if fallback.exists() {
+        match build.copy(&fallback, &output_dir.join(format!("{name}.png"))) {
+            Ok(()) => {
                 info!(
                     "char_img not found, but found in char_img_fallback: {name}.png"
                 );
-                File::create(format!("docs/char_img/fallback_{name}.txt")).unwrap();
+                build.write(&output_dir.join(format!("fallback_{name}.txt")), "")?;
             }
             Err(e) => {
-                eprintln!("Copying raw/char_img_fallback/{name}.png failed: {e}");
+                eprintln!("Copying {} failed: {e}", fallback.display());
             }
         }
     } else {
-        info!("char_img not found: {name}.png");
-        File::create(format!("docs/char_img/dummy_{name}.txt")).unwrap();
+        // reported as `missing-char-img` by `verify`
+        build.write(&output_dir.join(format!("dummy_{name}.txt")), "")?;
     }
-    if gen_link {
+    Ok(if gen_link {
         format!(
             r#"<a href="{rel_path}/char/{name}.html"><img src="{rel_path}/char_img/{name}.png" height="{size}"></a>"#,
         )
     } else {
-        format!(r#"<img src="{rel_path}/char_img/{name}.png" height="{size}">"#,)
-    }
+        format!(r#"<img src="{rel_path}/char_img/{name}.png" height="{size}">"#)
+    })
 }
 
-fn convert_hanzi_to_images(s: &str, exclude_list: &str, rel_path: &'static str) -> String {
-    convert_hanzi_to_images_with_size(s, exclude_list, rel_path, 30)
+fn convert_hanzi_to_images(
+    config: &Config,
+    build: &Build,
+    s: &str,
+    exclude_list: &str,
+    rel_path: &'static str,
+) -> anyhow::Result<String> {
+    convert_hanzi_to_images_with_size(config, build, s, exclude_list, rel_path, 30)
 }
 
 fn convert_hanzi_to_images_with_size(
+    config: &Config,
+    build: &Build,
     s: &str,
     exclude_list: &str,
     rel_path: &'static str,
     size: usize,
-) -> String {
+) -> anyhow::Result<String> {
+    let tokens = hanzi::tokenize(s)?;
     let mut ans = String::new();
-    let mut iter = s.chars();
-    let mut remove_following_space = false;
-    while let Some(c) = iter.next() {
-        if c == '∅' {
-            ans.push_str(&char_img_with_size("blank", rel_path, size, false));
-        } else if c == 'x' {
-            if Some('i') == iter.next() && Some('z') == iter.next() && Some('i') == iter.next() {
-                ans.push_str(&char_img_with_size("xi", rel_path, size, false));
-                ans.push_str(&char_img_with_size("zi", rel_path, size, false));
-                remove_following_space = true; // this deletes the redundant space after "xizi"
-            } else {
-                panic!("Expected `xizi` because `x` was encountered, but did not find it.");
+    HanziImages {
+        config,
+        build,
+        s,
+        exclude_list,
+        target: ImageTarget::Site { rel_path, size },
+    }
+    .push_tokens(&mut ans, &tokens)?;
+    Ok(ans)
+}
+
+/// Renders `s` in the same way as [`convert_hanzi_to_images`], except that the images are copied into `media_dir`,
+/// such as the media folder of an Anki deck, and referred to by their names prefixed with `media_prefix`.
+fn convert_hanzi_to_media_images(
+    config: &Config,
+    build: &Build,
+    s: &str,
+    exclude_list: &str,
+    media_dir: &Path,
+    media_prefix: &'static str,
+) -> anyhow::Result<String> {
+    let tokens = hanzi::tokenize(s)?;
+    let mut ans = String::new();
+    HanziImages {
+        config,
+        build,
+        s,
+        exclude_list,
+        target: ImageTarget::Media {
+            dir: media_dir,
+            prefix: media_prefix,
+        },
+    }
+    .push_tokens(&mut ans, &tokens)?;
+    Ok(ans)
+}
+
+/// Copies the image of `name` into `dir` as `{prefix}{name}.png` and refers to it by that name,
+/// or leaves `name` as text if there is no image for it.
+fn media_img(
+    config: &Config,
+    build: &Build,
+    name: &str,
+    dir: &Path,
+    prefix: &str,
+) -> std::io::Result<String> {
+    let media = format!("{prefix}{name}.png");
+    for source_dir in [&config.source.char_img, &config.source.char_img_fallback] {
+        let source = source_dir.join(format!("{name}.png"));
+        if source.exists() {
+            build.copy(&source, &dir.join(&media))?;
+            return Ok(format!(r#"<img src="{media}">"#));
+        }
+    }
+    // reported as `missing-char-img` by `verify`
+    Ok(name.to_owned())
+}
+
+/// Renders a phrase as a sequence of `<ruby>` elements, each of which puts a syllable over its linzklā.
+/// A contraction shows one syllable over several glyphs.
+fn convert_sentence_to_ruby(
+    config: &Config,
+    build: &Build,
+    sentence: &sentence::PekzepSentence,
+    rel_path: &'static str,
+) -> anyhow::Result<String> {
+    let mut ans = String::new();
+    let mut after_xizi = false;
+    for element in &sentence.0 {
+        match element {
+            sentence::Element::Syllable(syllable) => {
+                write!(
+                    ans,
+                    "<ruby><span style=\"filter:brightness(65%) contrast(500%);\">{}</span><rt>{}</rt></ruby>",
+                    convert_hanzi_to_images(
+                        config,
+                        build,
+                        &syllable.hanzi.to_string(),
+                        "() ",
+                        rel_path
+                    )?,
+                    syllable.latin
+                )?;
+                after_xizi = syllable.hanzi == sentence::SyllableHanzi::Xizi;
             }
-        } else if exclude_list.contains(c) {
-            if !(remove_following_space && c == ' ') {
-                ans.push(c);
+            sentence::Element::Punctuation(p) => {
+                // this deletes the redundant space after "xizi"
+                if !(after_xizi && p.trim().is_empty()) {
+                    write!(
+                        ans,
+                        "<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>",
+                        convert_hanzi_to_images(config, build, p, "() ", rel_path)?
+                    )?;
+                }
+                after_xizi = false;
             }
+        }
+    }
+    Ok(ans)
+}
+
+/// Where [`HanziImages`] copies the images to and how it refers to them
+#[derive(Clone, Copy)]
+enum ImageTarget<'a> {
+    /// `char_img/` in `docs`, referred to relative to `rel_path` with the height `size`, each linzklar linking to its page
+    Site { rel_path: &'static str, size: usize },
+
+    /// a folder of media such as that of an Anki deck, referred to by the names prefixed with `prefix`
+    Media { dir: &'a Path, prefix: &'static str },
+}
+
+/// Renders the tokens of `s`; the characters in `exclude_list` are left as they are, and the others are turned into images.
+struct HanziImages<'a> {
+    config: &'a Config,
+    build: &'a Build,
+    s: &'a str,
+    exclude_list: &'a str,
+    target: ImageTarget<'a>,
+}
+
+impl HanziImages<'_> {
+    fn push_tokens(&self, ans: &mut String, tokens: &[hanzi::Token]) -> std::io::Result<()> {
+        let mut after_xizi = false;
+        for token in tokens {
+            match &token.kind {
+                hanzi::HanziToken::Space if after_xizi => {
+                    // this deletes the redundant space after "xizi"
+                }
+                hanzi::HanziToken::Linzklar(linzklar) => {
+                    self.push_image(ans, linzklar.as_char())?;
+                }
+                hanzi::HanziToken::Contraction(contraction) => {
+                    self.push_char(ans, '«')?;
+                    for linzklar in &contraction.0 {
+                        self.push_image(ans, linzklar.as_char())?;
+                    }
+                    self.push_char(ans, '»')?;
+                }
+                hanzi::HanziToken::Xizi => {
+                    ans.push_str(&self.img("xi", false)?);
+                    ans.push_str(&self.img("zi", false)?);
+                }
+                hanzi::HanziToken::Blank => ans.push_str(&self.img("blank", false)?),
+                hanzi::HanziToken::BraceGroup(inner) => {
+                    self.push_char(ans, '{')?;
+                    self.push_tokens(ans, inner)?;
+                    self.push_char(ans, '}')?;
+                }
+                hanzi::HanziToken::Punctuation(_)
+                | hanzi::HanziToken::Space
+                | hanzi::HanziToken::SplitMarker
+                | hanzi::HanziToken::Placeholder(_) => {
+                    for c in self.s[token.span.clone()].chars() {
+                        self.push_char(ans, c)?;
+                    }
+                }
+            }
+            after_xizi = token.kind == hanzi::HanziToken::Xizi;
+        }
+        Ok(())
+    }
+
+    fn push_char(&self, ans: &mut String, c: char) -> std::io::Result<()> {
+        if self.exclude_list.contains(c) {
+            ans.push(c);
+            Ok(())
         } else {
             if c.is_ascii() {
-                log::warn!("Unexpected ASCII character `{c}` in {s}");
+                log::warn!("Unexpected ASCII character `{c}` in {}", self.s);
             }
-            ans.push_str(&char_img_with_size(
-                &c.to_string(),
-                rel_path,
-                size,
-                Linzklar::is_suitable_charcode_for_linzklar(c),
-            ));
+            self.push_image(ans, c)
         }
     }
 
-    ans
+    fn push_image(&self, ans: &mut String, c: char) -> std::io::Result<()> {
+        ans.push_str(&self.img(
+            &c.to_string(),
+            Linzklar::is_suitable_charcode_for_linzklar(c),
+        )?);
+        Ok(())
+    }
+
+    fn img(&self, name: &str, is_linzklar: bool) -> std::io::Result<String> {
+        match self.target {
+            ImageTarget::Site { rel_path, size } => {
+                char_img_with_size(self.config, self.build, name, rel_path, size, is_linzklar)
+            }
+            ImageTarget::Media { dir, prefix } => {
+                media_img(self.config, self.build, name, dir, prefix)
+            }
+        }
+    }
 }
 
 fn sentence_decomposition_to_analysis_merging_unsplitted_compounds(
+    config: &Config,
+    build: &Build,
     sentence_decomposition: &[verify::DecompositionItem],
-) -> Vec<String> {
+) -> anyhow::Result<Vec<String>> {
     // When splittable compounds appear unsplitted, it is better to display them merged.
     /*
         我
@@ -221,7 +450,9 @@ fn sentence_decomposition_to_analysis_merging_unsplitted_compounds(
         if Some(SplittableCompoundInfo::FormerHalfHash)
             == decomposition_item.splittable_compound_info
             && Some(SplittableCompoundInfo::LatterHalfExclamation)
-                == sentence_decomposition[i + 1].splittable_compound_info
+                == sentence_decomposition
+                    .get(i + 1)
+                    .and_then(|next| next.splittable_compound_info)
         {
             // Splittable compounds appear unsplitted; it is better to display them merged.
             ans.push(
@@ -229,7 +460,9 @@ fn sentence_decomposition_to_analysis_merging_unsplitted_compounds(
                     splittable_compound_info: None,
                     ..(*decomposition_item).clone()
                 }
-                .to_tab_separated_with_splittable_compound_info_and_also_with_a_link(".."),
+                .to_tab_separated_with_splittable_compound_info_and_also_with_a_link(
+                    config, build, "..",
+                )?,
             );
             skip_flag = true;
         } else if skip_flag {
@@ -237,11 +470,13 @@ fn sentence_decomposition_to_analysis_merging_unsplitted_compounds(
         } else {
             ans.push(
                 decomposition_item
-                    .to_tab_separated_with_splittable_compound_info_and_also_with_a_link(".."),
+                    .to_tab_separated_with_splittable_compound_info_and_also_with_a_link(
+                        config, build, "..",
+                    )?,
             );
         }
     }
-    ans
+    Ok(ans)
 }
 
 fn remove_guillemets(a: &str) -> String {
@@ -251,19 +486,21 @@ fn remove_guillemets(a: &str) -> String {
 /// Generates `vocab_list.html`
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn generate_vocab_list(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create("docs/vocab_list.html")?;
+pub fn generate_vocab_list(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
     let mut html = vec![];
     for (_, vocab) in &data_bundle.vocab_ordered {
-        html.push(vocab.to_tab_separated("."));
+        html.push(vocab.to_tab_separated(config, build, ".")?);
     }
-    write!(
-        file,
-        "{}",
+    build.write(
+        &config.output.file("vocab_list.html"),
         VocabListTemplate {
-            vocab_html: &html.join("\n")
+            vocab_html: &html.join("\n"),
         }
-        .render()?
+        .render()?,
     )?;
     Ok(())
 }
@@ -271,8 +508,11 @@ pub fn generate_vocab_list(data_bundle: &verify::DataBundle) -> Result<(), Box<d
 /// Generates `char_list.html`
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn generate_char_list(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create("docs/char_list.html")?;
+pub fn generate_char_list(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
     let mut html = vec![];
     let rel_path = ".";
 
@@ -282,17 +522,22 @@ pub fn generate_char_list(data_bundle: &verify::DataBundle) -> Result<(), Box<dy
         html.push(format!(
             "{}\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{}",
             linzklar,
-            convert_hanzi_to_images(&format!("{linzklar}"), "/{} N()SL«»", rel_path),
+            convert_hanzi_to_images(
+                config,
+                build,
+                &format!("{linzklar}"),
+                "/{} N()SL«»",
+                rel_path
+            )?,
             size
         ));
     }
-    write!(
-        file,
-        "{}",
+    build.write(
+        &config.output.file("char_list.html"),
         CharListTemplate {
-            char_list_table: &html.join("\n")
+            char_list_table: &html.join("\n"),
         }
-        .render()?
+        .render()?,
     )?;
     Ok(())
 }
@@ -300,12 +545,16 @@ pub fn generate_char_list(data_bundle: &verify::DataBundle) -> Result<(), Box<dy
 /// Generates `index.html`
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn generate_index(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create("docs/index.html")?;
+pub fn generate_index(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
     let mut index = vec!["<abbr title=\"Audio available in Edge, Firefox, Chrome and Opera. / 在Edge、Firefox、Chrome和Opera中都可以听到录音。\">🔊<i class=\"fab fa-chrome\"></i><i class=\"fab fa-firefox-browser\"></i><i class=\"fab fa-edge\"></i><i class=\"fab fa-edge-legacy\"></i><i class=\"fab fa-opera\"></i></abbr>\t<abbr title=\"Audio available in Safari. / 在Safari中都可以听到录音。\">🔊<i class=\"fab fa-safari\"></i></abbr>\tanalysis\tphrase".to_string()];
     let mut how_many_glosses = 0;
     for verify::Rows3Item {
         syllables,
+        sentence: _,
         decomposition,
         row,
     } in &data_bundle.rows3
@@ -320,7 +569,12 @@ pub fn generate_index(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Er
             R::Ready
         } else {
             let filename = read::phrase::syllables_to_str_underscore(syllables);
-            if std::path::Path::new(&format!("docs/nonreviewed_sounds/{filename}.oga")).exists() {
+            if config
+                .output
+                .nonreviewed_sounds_dir()
+                .join(format!("{filename}.oga"))
+                .exists()
+            {
                 R::NonReviewed
             } else {
                 R::Missing
@@ -337,15 +591,32 @@ pub fn generate_index(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Er
         ));
     }
 
-    write!(
-        file,
-        "{}",
+    let lessons = data_bundle
+        .lessons
+        .iter()
+        .enumerate()
+        .map(|(i, lesson)| {
+            format!(
+                "{}\t<a href=\"lesson/{}.html\"><span lang=\"en\">{}</span></a>\t<span lang=\"zh-CN\">{}</span>\t<span lang=\"ja\">{}</span>\t{}",
+                i + 1,
+                i + 1,
+                lesson.title_en,
+                lesson.title_zh,
+                lesson.title_ja,
+                lesson.phrases.len()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    build.write(
+        &config.output.file("index.html"),
         IndTemplate {
             index: &index.join("\n"),
             length: index.len() - 1, /* subtract off the title row */
-            how_many_glosses
+            how_many_glosses,
+            lessons: &lessons.join("\n"),
         }
-        .render()?
+        .render()?,
     )?;
 
     Ok(())
@@ -354,15 +625,14 @@ pub fn generate_index(data_bundle: &verify::DataBundle) -> Result<(), Box<dyn Er
 /// Generates `raw.tsv`
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn write_condensed_csv() -> Result<(), Box<dyn Error>> {
+pub fn write_condensed_csv(config: &Config, build: &Build) -> anyhow::Result<()> {
     use csv::StringRecord;
-    use log::warn;
     use normalizer::{
         capitalize_first_char, normalize_a_b_dialogue, normalize_chinese_punctuation,
     };
     use read::phrase::Record;
     use std::io::BufReader;
-    let f = File::open("raw/Spoonfed Pekzep - SpoonfedPekzep.tsv")?;
+    let f = File::open(&config.source.phrase)?;
     let f = BufReader::new(f);
     let mut condensed_csv = String::new();
     for line in f.lines() {
@@ -378,11 +648,6 @@ pub fn write_condensed_csv() -> Result<(), Box<dyn Error>> {
             continue;
         }
 
-        // 未査読でもないのに転写が埋まってないやつは警告
-        if rec.pekzep_hanzi.is_empty() {
-            warn!("The transcription for `{}` is empty.", rec.pekzep_latin);
-        }
-
         if rec.requires_substitution.is_empty() {
             let _ = writeln!(
                 condensed_csv,
@@ -400,58 +665,85 @@ pub fn write_condensed_csv() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    std::fs::write("docs/raw.tsv", condensed_csv)?;
+    build.write(&config.output.file("raw.tsv"), condensed_csv)?;
     Ok(())
 }
 
-/// Generates `raw.js`
+/// Generates `raw.js`, which defines `RAW_DATA` for `search.html` and `12.html`.
+/// Every value is written as a JSON string, so that no backtick or `${` in the input can break the script.
 /// # Errors
 /// Will return `Err` if the file I/O fails or the render panics.
-pub fn write_condensed_js() -> Result<(), Box<dyn Error>> {
-    use csv::StringRecord;
+pub fn write_condensed_js(
+    data_bundle: &verify::DataBundle,
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
     use normalizer::{
         capitalize_first_char, normalize_a_b_dialogue, normalize_chinese_punctuation,
     };
-    use read::phrase::Record;
-    use std::io::BufReader;
-    let f = File::open("raw/Spoonfed Pekzep - SpoonfedPekzep.tsv")?;
-    let f = BufReader::new(f);
-    let mut js = String::from("const RAW_DATA = [\n");
-    for line in f.lines() {
-        // to prevent double quotes from vanishing, I do not read with CSV parser
-        let rec: Record =
-            StringRecord::from(line?.split('\t').collect::<Vec<_>>()).deserialize(None)?;
+    use read::phrase::FilePathType;
+    use serde_derive::Serialize as Ser;
+
+    #[derive(Ser)]
+    struct RawData<'a> {
+        english: &'a str,
+        pekzep_latin: &'a str,
+        pekzep_hanzi: String,
+        chinese_pinyin: String,
+        chinese_hanzi: String,
+        decomposed: String,
+        filetype: String,
+        recording_author: String,
+        pekzep_images: String,
+        japanese: &'a str,
+    }
 
-        // 未査読の行は飛ばす
-        if rec.pekzep_hanzi.contains('@') {
-            continue;
-        }
-        if rec.pekzep_latin.is_empty() {
+    let mut js = String::from("const RAW_DATA = [\n");
+    for verify::Rows3Item { syllables, row, .. } in &data_bundle.rows3 {
+        if row.requires_substitution {
             continue;
         }
-
-        if rec.requires_substitution.is_empty() {
-            // This is inherently insecure, but who cares?
-            let _ = writeln!(
-                js,
-                "\t{{english: `{}`, pekzep_latin: `{}`, pekzep_hanzi: `{}`, chinese_pinyin: `{}`, chinese_hanzi: `{}`, decomposed: `{}`, filetype: `{}`, recording_author: `{}`, pekzep_images: `{}`, japanese: `{}`}},",
-                rec.english,
-                rec.pekzep_latin,
-                remove_guillemets(&rec.pekzep_hanzi),
-                capitalize_first_char(&rec.chinese_pinyin),
-                normalize_a_b_dialogue(&normalize_chinese_punctuation(&rec.chinese_hanzi)),
-                rec.decomposed,
-                rec.filetype,
-                rec.recording_author,
-                convert_hanzi_to_images(&remove_guillemets(&rec.pekzep_hanzi), "() ", "."),
-                rec.japanese
-            );
-        }
+        let pekzep_hanzi = remove_guillemets(&row.pekzep_hanzi);
+        let data = RawData {
+            english: &row.english,
+            pekzep_latin: &row.pekzep_latin,
+            chinese_pinyin: capitalize_first_char(&row.chinese_pinyin),
+            chinese_hanzi: normalize_a_b_dialogue(&normalize_chinese_punctuation(
+                &row.chinese_hanzi,
+            )),
+            decomposed: row
+                .decomposed
+                .iter()
+                .map(read::phrase::SentenceGloss::to_debugtext)
+                .collect::<Vec<_>>()
+                .join(".."),
+            filetype: [(FilePathType::Wav, "wav"), (FilePathType::Oga, "oga")]
+                .into_iter()
+                .filter(|(filetype, _)| row.filetype.contains(filetype))
+                .map(|(_, name)| name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            recording_author: row
+                .recording_author
+                .as_ref()
+                .map_or_else(String::new, ToString::to_string),
+            pekzep_images: convert_hanzi_to_images(config, build, &pekzep_hanzi, "() ", ".")
+                .with_context(|| {
+                    format!(
+                        "while rendering the phrase {}",
+                        read::phrase::syllables_to_str_underscore(syllables)
+                    )
+                })?,
+            pekzep_hanzi,
+            japanese: &row.japanese,
+        };
+        let _ = writeln!(js, "\t{},", serde_json::to_string(&data)?);
     }
 
     js += "]\n";
 
-    std::fs::write("docs/raw.js", js)?;
+    build.write(&config.output.file("raw.js"), js)?;
     Ok(())
 }
 
@@ -460,7 +752,9 @@ pub fn write_condensed_js() -> Result<(), Box<dyn Error>> {
 /// Will return `Err` if the file I/O fails or the render panics.
 pub fn write_char_count_js<S: ::std::hash::BuildHasher>(
     char_count: &HashMap<Linzklar, usize, S>,
-) -> Result<(), Box<dyn Error>> {
+    config: &Config,
+    build: &Build,
+) -> anyhow::Result<()> {
     let mut js = String::from("const CHAR_COUNT = {\n");
 
     let mut char_count: Vec<_> = char_count.iter().collect();
@@ -472,7 +766,7 @@ pub fn write_char_count_js<S: ::std::hash::BuildHasher>(
 
     js += "}\n";
 
-    std::fs::write("docs/char_count.js", js)?;
+    build.write(&config.output.file("char_count.js"), js)?;
     Ok(())
 }
 
@@ -482,7 +776,7 @@ mod tests {
     fn test_split_at_slashslash() {
         use crate::split_at_slashslash;
         assert_eq!(
-            split_at_slashslash("行 // 道"),
+            split_at_slashslash("行 // 道").unwrap(),
             (String::from("行 "), String::from(" 道"))
         );
     }
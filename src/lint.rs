@@ -0,0 +1,393 @@
+use crate::diagnostic::{Cell, Diagnostic, Severity};
+use crate::read;
+use serde_derive::Deserialize as De;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How a diagnostic produced by a rule is treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, De)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// not reported at all
+    Allow,
+
+    /// reported as a warning
+    Warn,
+
+    /// reported as an error, which makes the verification fail
+    Deny,
+}
+
+/// A check run on each phrase that is not fatal by itself
+pub trait LintRule {
+    /// the id used in the diagnostics and in the configuration, such as `kan1`
+    fn id(&self) -> &str;
+
+    /// Returns the warnings found in `row`, which was read from `path`.
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic>;
+}
+
+/// The ids of the warnings that are emitted directly by [`crate::verify`] rather than by a [`LintRule`].
+/// Their levels can be configured all the same.
+pub const NON_RULE_WARNINGS: [&str; 11] = [
+    "nonrecommended-character",
+    "unused-word",
+    "stale-suppression",
+    // found by looking outside the tsv files or at the graded order
+    "missing-oga",
+    "unlinked-oga",
+    "missing-wav",
+    "unlinked-wav",
+    "missing-char-img",
+    "empty-transcription",
+    "too-many-new-words",
+    "undecomposed-lesson-phrase",
+];
+
+/// Requires one of the keywords to appear in the English translation whenever the linzklar is used,
+/// so that a translation that dropped the notion is caught.
+pub struct KeywordRule(pub read::lint_keyword::Item);
+
+impl LintRule for KeywordRule {
+    fn id(&self) -> &str {
+        &self.0.rule
+    }
+
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        let read::lint_keyword::Item {
+            rule,
+            linzklar,
+            keywords,
+        } = &self.0;
+        if keywords.iter().any(|k| row.english.contains(k.as_str())) {
+            return vec![];
+        }
+        let alternatives = keywords
+            .iter()
+            .map(|k| format!("'{k}'"))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let notion = keywords.first().map_or("", String::as_str);
+        row.pekzep_hanzi
+            .match_indices(linzklar.as_char())
+            .map(|(i, found)| {
+                Diagnostic::warning(
+                    rule,
+                    path,
+                    row.line,
+                    format!(
+                        "{} contains {linzklar}, but the English translation did not contain the word {alternatives}. Please check if the sentence `{}` should contains the notion of '{notion}'.",
+                        row.pekzep_hanzi, row.english
+                    ),
+                )
+                .with_cell(Cell::new("pekzep_hanzi", &row.pekzep_hanzi, i..i + found.len()))
+            })
+            .collect()
+    }
+}
+
+/// 【之】の後に句読点あるなら警告
+pub struct ABeforePunctuation;
+
+impl LintRule for ABeforePunctuation {
+    fn id(&self) -> &'static str {
+        "a-before-punctuation"
+    }
+
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        use regex::Regex;
+        use std::sync::LazyLock;
+        static RE_PUNCTUATION_AFTER_之: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new("之[。！？」]").unwrap());
+        let s = &row.pekzep_hanzi;
+        RE_PUNCTUATION_AFTER_之
+            .find_iter(s)
+            .map(|m| {
+                Diagnostic::warning(
+                    self.id(),
+                    path,
+                    row.line,
+                    format!(
+                        "punctuation after `之` is detected in `{s}`. Maybe replace it with `噫`?"
+                    ),
+                )
+                .with_cell(Cell::new(
+                    "pekzep_hanzi",
+                    s,
+                    m.start()..m.start() + '之'.len_utf8(),
+                ))
+                .with_suggestion("replace `之` with `噫`")
+            })
+            .collect()
+    }
+}
+
+/// 【噫】の後に句読点も)もないなら警告
+pub struct AWithoutPunctuation;
+
+impl LintRule for AWithoutPunctuation {
+    fn id(&self) -> &'static str {
+        "a-without-punctuation"
+    }
+
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        use regex::Regex;
+        use std::sync::LazyLock;
+        static RE_NO_PUNCTUATION_AFTER_噫: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new("噫[^)。！？」]").unwrap());
+        let s = &row.pekzep_hanzi;
+        RE_NO_PUNCTUATION_AFTER_噫
+            .find_iter(s)
+            .map(|m| {
+                Diagnostic::warning(
+                    self.id(),
+                    path,
+                    row.line,
+                    format!(
+                        "no punctuation found after `噫` in `{s}`. Maybe replace it with `之`."
+                    ),
+                )
+                .with_cell(Cell::new(
+                    "pekzep_hanzi",
+                    s,
+                    m.start()..m.start() + '噫'.len_utf8(),
+                ))
+                .with_suggestion("replace `噫` with `之`")
+            })
+            .collect()
+    }
+}
+
+pub struct SpaceBeforePunctuation;
+
+impl LintRule for SpaceBeforePunctuation {
+    fn id(&self) -> &'static str {
+        "space-before-punctuation"
+    }
+
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        let mut ans = vec![];
+        for (field, s) in [
+            ("pekzep_latin", &row.pekzep_latin),
+            ("english", &row.english),
+            ("chinese_pinyin", &row.chinese_pinyin),
+        ] {
+            for (pattern, name) in [(" .", "period"), (" ,", "comma")] {
+                for (i, _) in s.match_indices(pattern) {
+                    ans.push(
+                        Diagnostic::warning(
+                            self.id(),
+                            path,
+                            row.line,
+                            format!(
+                                "a space before a {name} is detected in `{s}`. Remove the space."
+                            ),
+                        )
+                        .with_cell(Cell::new(field, s, i..i + 1))
+                        .with_suggestion("remove the space"),
+                    );
+                }
+            }
+        }
+        ans
+    }
+}
+
+/// The pinyin does not match the Chinese translation
+pub struct PinyinMismatch;
+
+impl LintRule for PinyinMismatch {
+    fn id(&self) -> &'static str {
+        "pinyin-mismatch"
+    }
+
+    fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        use log::info;
+        use match_pinyin_with_hanzi::match_pinyin_with_hanzi;
+        info!("parsing pinyin {:?}:", &row.chinese_pinyin);
+        match match_pinyin_with_hanzi(&row.chinese_pinyin, &row.chinese_hanzi) {
+            Ok(()) => vec![],
+            Err(err) => vec![
+                Diagnostic::warning(self.id(), path, row.line, err)
+                    .with_cell(Cell::whole("chinese_pinyin", &row.chinese_pinyin)),
+            ],
+        }
+    }
+}
+
+/// All the [`LintRule`]s that are run on the phrases
+pub struct Registry {
+    rules: Vec<Box<dyn LintRule>>,
+}
+
+impl Registry {
+    /// The built-in rules, followed by a [`KeywordRule`] for each row of "lint-keywords.tsv"
+    #[must_use]
+    pub fn new(keywords: Vec<read::lint_keyword::Item>) -> Self {
+        let mut rules: Vec<Box<dyn LintRule>> = vec![
+            Box::new(ABeforePunctuation),
+            Box::new(AWithoutPunctuation),
+            Box::new(SpaceBeforePunctuation),
+            Box::new(PinyinMismatch),
+        ];
+        rules.extend(
+            keywords
+                .into_iter()
+                .map(|item| Box::new(KeywordRule(item)) as Box<dyn LintRule>),
+        );
+        Self { rules }
+    }
+
+    /// Whether `id` names a rule, including those listed in [`NON_RULE_WARNINGS`]
+    #[must_use]
+    pub fn knows(&self, id: &str) -> bool {
+        NON_RULE_WARNINGS.contains(&id) || self.rules.iter().any(|rule| rule.id() == id)
+    }
+
+    #[must_use]
+    pub fn check(&self, path: &Path, row: &read::phrase::Item) -> Vec<Diagnostic> {
+        self.rules
+            .iter()
+            .flat_map(|rule| rule.check(path, row))
+            .collect()
+    }
+}
+
+/// The exceptions listed in "lint-suppressions.tsv", keeping track of which of them are still needed
+pub struct Suppressions {
+    items: Vec<read::lint_suppression::Item>,
+    used: Vec<bool>,
+}
+
+impl Suppressions {
+    #[must_use]
+    pub fn new(items: Vec<read::lint_suppression::Item>) -> Self {
+        let used = vec![false; items.len()];
+        Self { items, used }
+    }
+
+    /// Drops the warnings on the phrase `key` that are suppressed, marking the suppressions as used.
+    pub fn filter(&mut self, key: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter(|d| {
+                if d.severity != Severity::Warning {
+                    return true;
+                }
+                let mut suppressed = false;
+                for (item, used) in self.items.iter().zip(self.used.iter_mut()) {
+                    if item.phrase == key && item.rule == d.rule {
+                        *used = true;
+                        suppressed = true;
+                    }
+                }
+                !suppressed
+            })
+            .collect()
+    }
+
+    /// Reports the suppressions that did not suppress anything, since they can be removed from `path`.
+    #[must_use]
+    pub fn stale(&self, path: &Path) -> Vec<Diagnostic> {
+        self.items
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, used)| !**used)
+            .map(|(item, _)| {
+                Diagnostic::warning(
+                    "stale-suppression",
+                    path,
+                    item.line,
+                    format!(
+                        "`{}` no longer triggers `{}`, so the suppression is not needed anymore",
+                        item.phrase, item.rule
+                    ),
+                )
+                .with_cell(Cell::whole("phrase", &item.phrase))
+                .with_suggestion("remove this line")
+            })
+            .collect()
+    }
+}
+
+/// The `[lints]` section of the configuration file, mapping a rule id to its level:
+/// ```toml
+/// [lints]
+/// kan1 = "allow"
+/// space-before-punctuation = "deny"
+/// ```
+/// The rules not listed are `warn`.
+#[derive(Debug, Clone, Default, De)]
+#[serde(transparent)]
+pub struct LintLevels(pub HashMap<String, Level>);
+
+impl LintLevels {
+    /// # Errors
+    /// Fails if a rule unknown to `registry` is configured.
+    pub fn validate(&self, registry: &Registry) -> anyhow::Result<()> {
+        let mut unknown = self
+            .0
+            .keys()
+            .filter(|id| !registry.knows(id))
+            .map(|id| format!("`{id}`"))
+            .collect::<Vec<_>>();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            unknown.sort();
+            Err(anyhow::anyhow!(
+                "unknown lint rule(s) in the configuration: {}",
+                unknown.join(", ")
+            ))
+        }
+    }
+
+    /// Drops the allowed warnings and turns the denied ones into errors. Errors are left untouched.
+    #[must_use]
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter_map(|mut d| {
+                if d.severity == Severity::Warning {
+                    match self.0.get(&d.rule).copied().unwrap_or(Level::Warn) {
+                        Level::Allow => return None,
+                        Level::Warn => {}
+                        Level::Deny => d.severity = Severity::Error,
+                    }
+                }
+                Some(d)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, LintLevels, Registry};
+    use crate::diagnostic::{Diagnostic, Severity};
+    use std::path::Path;
+
+    #[test]
+    fn test_apply() {
+        let levels = LintLevels(
+            [("kan1", Level::Allow), ("co1", Level::Deny)]
+                .into_iter()
+                .map(|(id, level)| (id.to_owned(), level))
+                .collect(),
+        );
+        let path = Path::new("a.tsv");
+        let applied = levels.apply(vec![
+            Diagnostic::warning("kan1", path, 1, ""),
+            Diagnostic::warning("co1", path, 2, ""),
+            Diagnostic::warning("a-before-punctuation", path, 3, ""),
+        ]);
+        assert_eq!(
+            applied
+                .iter()
+                .map(|d| (d.line, d.severity))
+                .collect::<Vec<_>>(),
+            [(2, Severity::Error), (3, Severity::Warning)]
+        );
+        assert!(levels.validate(&Registry::new(vec![])).is_err());
+    }
+}
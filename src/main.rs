@@ -1,62 +1,562 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use spoonfed_pekzep::config::{Config, OutputPaths, SourcePaths};
+use spoonfed_pekzep::diagnostic::{Diagnostic, Diagnostics, Severity};
+use spoonfed_pekzep::read::phrase::{encode_to_pekzep_syllables, syllables_to_str_underscore};
 use spoonfed_pekzep::*;
-use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
 
-fn reset_folder(path: &str) -> Result<(), Box<dyn Error>> {
-    eprintln!("Resetting {path}");
-    std::fs::remove_dir_all(path)?;
-    std::fs::create_dir(path)?;
-    Ok(())
+/// The input data is malformed or inconsistent (`EX_DATAERR` in sysexits.h)
+const EXIT_DATA_ERROR: u8 = 65;
+
+/// Reading or writing a file failed (`EX_IOERR` in sysexits.h)
+const EXIT_IO_ERROR: u8 = 74;
+
+/// `lookup` found nothing
+const EXIT_NOT_FOUND: u8 = 1;
+
+#[derive(Parser)]
+#[command(
+    about = "Generates the Spoonfed Pekzep website from the spreadsheets",
+    after_help = "Exit status: 0 on success, 1 if `lookup` found nothing, 2 on invalid usage, 65 if the data is invalid (or if any warning is found under --deny-warnings), 74 if a file cannot be read or written."
+)]
+struct Args {
+    /// A TOML file specifying where to read the input from and where to write the output to
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Reads the input files from this folder instead of `raw/`
+    #[arg(long, global = true, value_name = "DIR")]
+    raw: Option<PathBuf>,
+
+    /// Writes the output files to this folder instead of `docs/`
+    #[arg(long, global = true, value_name = "DIR")]
+    docs: Option<PathBuf>,
+
+    /// How to print the errors and the warnings found in the input
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+
+    /// Fails (with exit status 65) if any warning is found in the input, before anything is written
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
-fn main() -> Result<(), Box<dyn Error>> {
-    use std::env;
-    if env::var("RUST_LOG").is_err() {
-        // TODO: Audit that the environment access only happens in single-threaded code.
-        unsafe { env::set_var("RUST_LOG", "warn") };
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MessageFormat {
+    /// Like rustc, to the standard error
+    Human,
+
+    /// One JSON object per line, to the standard output
+    Json,
+}
+
+impl MessageFormat {
+    fn print(self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            match self {
+                Self::Human => eprintln!("{}", diagnostic.render_human()),
+                Self::Json => println!("{}", diagnostic.render_json()),
+            }
+        }
     }
-    env_logger::init();
+}
 
-    // At each run, reset the content of docs/char_img,
-    // because later in the script this folder is automatically filled
-    // by the images required to render the page.
-    reset_folder("docs/char_img")?;
+/// Prints the warnings as they are found, and keeps them for the summary at the end.
+struct Reporter {
+    format: MessageFormat,
+    warnings: Vec<Diagnostic>,
+    deny_warnings: bool,
+}
 
-    // At each run, reset the content of docs/phrase, docs/vocab and docs/char
-    // because they will be filled later in the script.
-    reset_folder("docs/phrase")?;
-    reset_folder("docs/char")?;
-    reset_folder("docs/vocab")?;
+impl Reporter {
+    fn report(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.format.print(&diagnostics);
+        self.warnings.extend(diagnostics);
+    }
 
-    let data_bundle = verify::DataBundle::new()?;
+    /// Prints how many warnings were found for each rule.
+    /// Called once the input is verified, so that a denied warning stops the command before it writes anything.
+    /// # Errors
+    /// Fails if any warning was found and `deny_warnings` is set.
+    fn finish(&self) -> anyhow::Result<()> {
+        use std::collections::BTreeMap;
+        if self.warnings.is_empty() {
+            return Ok(());
+        }
+        let mut count = BTreeMap::new();
+        for warning in &self.warnings {
+            *count.entry(warning.rule.as_str()).or_insert(0_usize) += 1;
+        }
+        eprintln!("{} warning(s) found:", self.warnings.len());
+        for (rule, n) in &count {
+            eprintln!("  {rule}: {n}");
+        }
+        if self.deny_warnings {
+            anyhow::bail!(
+                "{} warning(s) found, which are denied by --deny-warnings",
+                self.warnings.len()
+            );
+        }
+        Ok(())
+    }
+}
 
-    eprintln!("Generating docs/phrase/");
-    generate::phrases::r#gen(&data_bundle)?;
+#[derive(Subcommand)]
+enum Command {
+    /// Verifies the input and regenerates the whole website (default)
+    Build,
 
-    eprintln!("Generating docs/vocab/");
-    generate::vocabs::r#gen(&data_bundle)?;
+    /// Only verifies the input, without writing anything
+    Check,
 
-    eprintln!("Generating docs/vocab_list_internal.html");
-    generate::vocab_list_internal::r#gen(&data_bundle)?;
+    /// Prints the numbers describing the corpus, one `name<TAB>value` per line
+    Stats,
 
-    eprintln!("Generating docs/vocab_list.html");
-    generate_vocab_list(&data_bundle)?;
+    /// Prints the phrases and the words matching the query, one tab-separated line each
+    Lookup {
+        /// Pekzep in Latin (`mua2 zep1`), in hanzi (`汝言`), a phrase key (`mua2_zep1_nan2`) or an internal word key (`於dur`)
+        query: String,
+    },
 
-    eprintln!("Generating docs/char/");
-    generate::chars::r#gen(&data_bundle)?;
+    /// Exports the phrases and the words as a deck that Anki can import
+    Anki {
+        /// The folder to write `sentences.txt`, `vocab.txt` and `media/` to
+        #[arg(long, value_name = "DIR", default_value = "anki")]
+        out: PathBuf,
+    },
 
-    eprintln!("Generating docs/char_list.html");
-    generate_char_list(&data_bundle)?;
+    /// Builds the website, serves it at http://127.0.0.1:PORT and rebuilds it whenever the input changes
+    Serve {
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+}
 
-    eprintln!("Generating docs/index.html");
-    generate_index(&data_bundle)?;
+impl Args {
+    /// The command-line flags take precedence over the configuration file.
+    fn to_config(&self) -> anyhow::Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        if let Some(raw) = &self.raw {
+            config.source = SourcePaths::in_dir(raw);
+        }
+        if let Some(docs) = &self.docs {
+            config.output = OutputPaths::in_dir(docs);
+        }
+        Ok(config)
+    }
+}
 
-    eprintln!("Writing docs/raw.tsv");
-    write_condensed_csv()?;
+/// Tells "I/O error" from "data error" by looking for an `std::io::Error` in the chain of causes.
+fn exit_code_for(err: &anyhow::Error) -> u8 {
+    if err
+        .chain()
+        .any(|e| e.downcast_ref::<std::io::Error>().is_some())
+    {
+        EXIT_IO_ERROR
+    } else {
+        EXIT_DATA_ERROR
+    }
+}
 
-    eprintln!("Writing docs/raw.js");
-    write_condensed_js()?;
+/// Reads and verifies the input, reporting the warnings found.
+fn load(config: &Config, reporter: &mut Reporter) -> anyhow::Result<verify::DataBundle> {
+    let data_bundle = verify::DataBundle::new(&config.source, &config.lints)?;
+    reporter.report(data_bundle.diagnostics.clone());
+    Ok(data_bundle)
+}
 
-    eprintln!("Writing docs/char_count.js");
-    write_char_count_js(&data_bundle.char_count)?;
+/// Runs the checks left out of `load`, since `lookup` and `stats` need neither the graded order nor the recordings.
+fn check_corpus(
+    config: &Config,
+    reporter: &mut Reporter,
+    data_bundle: &verify::DataBundle,
+) -> anyhow::Result<()> {
+    let mut diagnostics = data_bundle.check_graded(&config.source.phrase, &config.graded);
+    diagnostics.extend(data_bundle.check_sounds(&config.source.phrase, &config.output));
+    diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    let diagnostics = config.lints.apply(diagnostics);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return Err(Diagnostics(diagnostics).into());
+    }
+    reporter.report(diagnostics);
+    Ok(())
+}
 
+/// The folders filled with one file per phrase, word, linzklar or lesson, from which the files no longer generated are removed
+fn managed_folders(config: &Config) -> [PathBuf; 5] {
+    [
+        config.output.char_img_dir(),
+        config.output.phrase_dir(),
+        config.output.char_dir(),
+        config.output.vocab_dir(),
+        config.output.lesson_dir(),
+    ]
+}
+
+fn build(config: &Config, reporter: &mut Reporter) -> anyhow::Result<()> {
+    build_only(config, reporter, &serve::Generator::ALL)
+}
+
+/// Verifies the input and runs `generators`, keeping the files that the other generators wrote in the previous build.
+fn build_only(
+    config: &Config,
+    reporter: &mut Reporter,
+    generators: &[serve::Generator],
+) -> anyhow::Result<()> {
+    use serve::Generator;
+    let docs = config.output.docs.display();
+
+    let data_bundle = load(config, reporter)?;
+    check_corpus(config, reporter, &data_bundle)?;
+    reporter.finish()?;
+
+    // Only the files whose content has changed since the previous build are rewritten,
+    // and the files no longer generated are removed at the end.
+    for folder in managed_folders(config) {
+        std::fs::create_dir_all(folder)?;
+    }
+    let build = manifest::Build::begin(&config.output.docs)?;
+    let folders = if generators.len() == Generator::ALL.len() {
+        managed_folders(config).to_vec()
+    } else {
+        let folders = generators
+            .iter()
+            .filter_map(|generator| generator.folder(config))
+            .collect::<Vec<_>>();
+        build.keep_previous_except(&folders);
+        folders
+    };
+
+    for generator in generators {
+        match generator {
+            Generator::Phrases => {
+                eprintln!("Generating {docs}/phrase/");
+                generate::phrases::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::Vocabs => {
+                eprintln!("Generating {docs}/vocab/");
+                generate::vocabs::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::VocabListInternal => {
+                eprintln!("Generating {docs}/vocab_list_internal.html");
+                generate::vocab_list_internal::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::VocabList => {
+                eprintln!("Generating {docs}/vocab_list.html");
+                generate_vocab_list(&data_bundle, config, &build)?;
+            }
+            Generator::Dictionary => {
+                eprintln!("Generating {docs}/dictionary.html");
+                generate::dictionary::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::Chars => {
+                eprintln!("Generating {docs}/char/");
+                generate::chars::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::CharList => {
+                eprintln!("Generating {docs}/char_list.html");
+                generate_char_list(&data_bundle, config, &build)?;
+            }
+            Generator::Index => {
+                eprintln!("Generating {docs}/index.html");
+                generate_index(&data_bundle, config, &build)?;
+            }
+            Generator::Lessons => {
+                eprintln!("Generating {docs}/lesson/");
+                generate::lessons::r#gen(&data_bundle, config, &build)?;
+                generate::worksheets::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::Graded => {
+                eprintln!("Generating {docs}/graded_index.html");
+                generate::graded::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::RawTsv => {
+                eprintln!("Writing {docs}/raw.tsv");
+                write_condensed_csv(config, &build)?;
+            }
+            Generator::RawJs => {
+                eprintln!("Writing {docs}/raw.js");
+                write_condensed_js(&data_bundle, config, &build)?;
+            }
+            Generator::Corpus => {
+                eprintln!("Writing {docs}/corpus.json");
+                generate::corpus::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::Cloze => {
+                eprintln!("Writing {docs}/cloze.json and {docs}/cloze.html");
+                generate::cloze::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::Quiz => {
+                eprintln!("Writing {docs}/quiz.html and {docs}/quiz.js");
+                generate::quiz::r#gen(&data_bundle, config, &build)?;
+            }
+            Generator::CharCountJs => {
+                eprintln!("Writing {docs}/char_count.js");
+                write_char_count_js(&data_bundle.char_count, config, &build)?;
+            }
+            Generator::Interlinear => {
+                eprintln!("Writing {docs}/interlinear.tex");
+                generate::interlinear::r#gen(&data_bundle, config, &build)?;
+            }
+        }
+    }
+
+    let summary = build.finish(&folders)?;
+    eprintln!(
+        "Wrote {} file(s), left {} unchanged and removed {}.",
+        summary.written, summary.unchanged, summary.removed
+    );
+
+    Ok(())
+}
+
+fn check(config: &Config, reporter: &mut Reporter) -> anyhow::Result<()> {
+    let data_bundle = load(config, reporter)?;
+    check_corpus(config, reporter, &data_bundle)?;
+    reporter.finish()?;
+    eprintln!(
+        "OK: {} phrases and {} words are consistent.",
+        data_bundle.rows3.len(),
+        data_bundle.vocab_ordered.len()
+    );
     Ok(())
 }
+
+fn stats(config: &Config, reporter: &mut Reporter) -> anyhow::Result<()> {
+    use read::phrase::FilePathType;
+    let data_bundle = load(config, reporter)?;
+    reporter.finish()?;
+    let rows3 = &data_bundle.rows3;
+    let count_rows =
+        |pred: &dyn Fn(&verify::Rows3Item) -> bool| rows3.iter().filter(|r| pred(r)).count();
+
+    println!("phrases\t{}", rows3.len());
+    println!(
+        "phrases_glossed\t{}",
+        count_rows(&|r| !r.decomposition.is_empty())
+    );
+    println!(
+        "phrases_with_wav\t{}",
+        count_rows(&|r| r.row.filetype.contains(&FilePathType::Wav))
+    );
+    println!(
+        "phrases_with_oga\t{}",
+        count_rows(&|r| r.row.filetype.contains(&FilePathType::Oga))
+    );
+    println!("words_used\t{}", data_bundle.vocab_ordered.len());
+    println!(
+        "word_occurrences\t{}",
+        data_bundle.vocab_count.values().sum::<usize>()
+    );
+    println!("linzklar_used\t{}", data_bundle.char_count.len());
+    println!(
+        "linzklar_occurrences\t{}",
+        data_bundle.char_count.values().sum::<usize>()
+    );
+    Ok(())
+}
+
+fn anki(config: &Config, reporter: &mut Reporter, out: &Path) -> anyhow::Result<()> {
+    let data_bundle = load(config, reporter)?;
+    reporter.finish()?;
+    eprintln!("Writing the Anki deck to {}", out.display());
+    let build = manifest::Build::begin(out)?;
+    generate::anki::export(&data_bundle, config, &build, out)?;
+    let summary = build.finish(&[out.join("media")])?;
+    eprintln!(
+        "Wrote {} file(s), left {} unchanged and removed {}.",
+        summary.written, summary.unchanged, summary.removed
+    );
+    Ok(())
+}
+
+/// Runs `generators` once and records the outcome into `status`, keeping the errors for the diagnostics page instead of stopping.
+/// Returns whether the build succeeded.
+fn rebuild(
+    config: &Config,
+    format: MessageFormat,
+    status: &Mutex<serve::Status>,
+    generators: &[serve::Generator],
+) -> bool {
+    // the preview server keeps serving the pages whatever warnings are found
+    let mut reporter = Reporter {
+        format,
+        warnings: vec![],
+        deny_warnings: false,
+    };
+    let result = build_only(config, &mut reporter, generators);
+    let mut status = status.lock().unwrap();
+    status.build_number += 1;
+    status.diagnostics = reporter.warnings;
+    status.failure = None;
+    match result {
+        Ok(()) => {
+            eprintln!("Build #{} done.", status.build_number);
+            true
+        }
+        Err(e) => {
+            if let Some(diagnostics) = e.downcast_ref::<Diagnostics>() {
+                format.print(&diagnostics.0);
+                status.diagnostics.extend(diagnostics.0.iter().cloned());
+            } else {
+                eprintln!("Error: {e}");
+                status.failure = Some(e.to_string());
+            }
+            eprintln!(
+                "Build #{} failed. See {} on the preview server for the details.",
+                status.build_number,
+                serve::DIAGNOSTICS_PATH
+            );
+            false
+        }
+    }
+}
+
+/// Never returns unless the port cannot be bound.
+///
+/// A change in the input reruns the verification and the generators reading it, and a failed build is followed by a full one.
+/// A change in the templates is only noticed, since they are compiled into the binary.
+fn serve(config: &Config, format: MessageFormat, port: u16) -> anyhow::Result<()> {
+    use serve::Generator;
+    use std::sync::Arc;
+    use std::time::Duration;
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    let status = Arc::new(Mutex::new(serve::Status::default()));
+    let mut succeeded = rebuild(config, format, &status, &Generator::ALL);
+    eprintln!(
+        "Serving {} at http://127.0.0.1:{port}/",
+        config.output.docs.display()
+    );
+    serve::spawn_workers(&listener, &config.output.docs, &status)?;
+
+    let inputs = serve::watched_inputs(config);
+    let templates = [PathBuf::from(serve::TEMPLATES_DIR)];
+    let (mut inputs_snapshot, templates_snapshot) =
+        (serve::snapshot(&inputs), serve::snapshot(&templates));
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        if !status.lock().unwrap().templates_changed
+            && serve::snapshot(&templates) != templates_snapshot
+        {
+            eprintln!(
+                "{} has changed. The templates are compiled into the binary, so rebuild it and restart `serve` to see the change.",
+                serve::TEMPLATES_DIR
+            );
+            let mut status = status.lock().unwrap();
+            status.templates_changed = true;
+            // reloads the pages so that they show the notice
+            status.build_number += 1;
+        }
+        let current = serve::snapshot(&inputs);
+        if current != inputs_snapshot {
+            let changed = serve::changed_paths(&inputs_snapshot, &current);
+            inputs_snapshot = current;
+            // the generators skipped by a failed build have to run as well
+            let generators = if succeeded {
+                serve::affected_generators(config, &changed)
+            } else {
+                Generator::ALL.to_vec()
+            };
+            eprintln!(
+                "{} input file(s) have changed. Rerunning the verification and {} generator(s).",
+                changed.len(),
+                generators.len()
+            );
+            succeeded = rebuild(config, format, &status, &generators);
+        }
+    }
+}
+
+/// Returns whether anything matched.
+fn lookup(config: &Config, reporter: &mut Reporter, query: &str) -> anyhow::Result<bool> {
+    let data_bundle = load(config, reporter)?;
+    reporter.finish()?;
+    let query = query.trim();
+    let query_syllables = encode_to_pekzep_syllables(query)
+        .ok()
+        .filter(|s| !s.is_empty());
+    let is_hanzi_query = !query.is_ascii();
+    let mut found = false;
+
+    for verify::Rows3Item { syllables, row, .. } in &data_bundle.rows3 {
+        let key = syllables_to_str_underscore(syllables);
+        let matches_latin = query_syllables.as_ref().is_some_and(|query_syllables| {
+            syllables
+                .windows(query_syllables.len())
+                .any(|w| w == query_syllables.as_slice())
+        });
+        let matches_hanzi = is_hanzi_query && row.pekzep_hanzi.contains(query);
+        if key == query || matches_latin || matches_hanzi {
+            found = true;
+            println!(
+                "phrase\t{key}\t{}\t{}\t{}",
+                row.pekzep_latin, row.pekzep_hanzi, row.english
+            );
+        }
+    }
+
+    for (key, vocab) in &data_bundle.vocab_ordered {
+        let matches_hanzi = is_hanzi_query && vocab.pekzep_hanzi.contains(query);
+        if key.to_string() == query || vocab.pekzep_latin == query || matches_hanzi {
+            found = true;
+            println!(
+                "vocab\t{key}\t{}\t{}\t{}\t{}",
+                vocab.pekzep_latin, vocab.pekzep_hanzi, vocab.parts_of_speech, vocab.english_gloss
+            );
+        }
+    }
+    Ok(found)
+}
+
+fn main() -> ExitCode {
+    use std::env;
+    let args = Args::parse();
+    if env::var("RUST_LOG").is_err() {
+        // TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { env::set_var("RUST_LOG", "warn") };
+    }
+    env_logger::init();
+
+    let mut reporter = Reporter {
+        format: args.message_format,
+        warnings: vec![],
+        deny_warnings: args.deny_warnings,
+    };
+    let result = args.to_config().and_then(|config| match &args.command {
+        None | Some(Command::Build) => build(&config, &mut reporter).map(|()| true),
+        Some(Command::Check) => check(&config, &mut reporter).map(|()| true),
+        Some(Command::Stats) => stats(&config, &mut reporter).map(|()| true),
+        Some(Command::Lookup { query }) => lookup(&config, &mut reporter, query),
+        Some(Command::Anki { out }) => anki(&config, &mut reporter, out).map(|()| true),
+        Some(Command::Serve { port }) => serve(&config, args.message_format, *port).map(|()| true),
+    });
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_NOT_FOUND),
+        Err(e) => {
+            if let Some(diagnostics) = e.downcast_ref::<Diagnostics>() {
+                args.message_format.print(&diagnostics.0);
+                eprintln!(
+                    "Error: found {} error(s) and {} warning(s) in the input",
+                    diagnostics.count(Severity::Error),
+                    diagnostics.count(Severity::Warning)
+                );
+                return ExitCode::from(EXIT_DATA_ERROR);
+            }
+            eprintln!("Error: {e}");
+            for cause in e.chain().skip(1) {
+                eprintln!("Caused by: {cause}");
+            }
+            ExitCode::from(exit_code_for(&e))
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use serde_derive::{Deserialize as De, Serialize as Ser};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The name of the manifest, placed directly under `docs/`
+pub const FILE_NAME: &str = ".build_manifest.json";
+
+/// The hashes of the files written by the previous build, keyed by their paths relative to `docs/`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Ser, De)]
+pub struct Manifest {
+    pub files: BTreeMap<String, String>,
+}
+
+/// How many files a build has touched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub written: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+/// The files written so far by a [`Build`]
+#[derive(Debug, Default)]
+struct Progress {
+    current: Manifest,
+    summary: Summary,
+}
+
+/// A build writing into `docs`, which skips the files whose contents are the same as in the previous build.
+///
+/// Only the writes are skipped: every page is still rendered, and its contents are hashed to compare them with the manifest.
+#[derive(Debug)]
+pub struct Build {
+    docs: PathBuf,
+    previous: Manifest,
+    progress: Mutex<Progress>,
+}
+
+fn hash(contents: &[u8]) -> String {
+    format!("{:016x}", crate::fnv1a(contents))
+}
+
+impl Build {
+    /// Starts recording the files written into `docs`, reading the manifest of the previous build if there is one.
+    ///
+    /// The manifest is removed until [`Build::finish`] writes it again, so that a build that fails halfway is followed by a full one.
+    /// # Errors
+    /// Fails if the manifest exists but cannot be read.
+    pub fn begin(docs: &Path) -> anyhow::Result<Self> {
+        let path = docs.join(FILE_NAME);
+        let previous = if path.exists() {
+            let previous = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            std::fs::remove_file(&path)?;
+            previous
+        } else {
+            Manifest::default()
+        };
+        Ok(Self {
+            docs: docs.to_owned(),
+            previous,
+            progress: Mutex::default(),
+        })
+    }
+
+    fn key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.docs)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Writes `contents` into `path` unless the previous build wrote the same contents there.
+    /// # Errors
+    /// Fails if the file I/O fails.
+    /// # Panics
+    /// Panics if another thread panicked while writing a file.
+    pub fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+        let contents = contents.as_ref();
+        let key = self.key(path);
+        let hash = hash(contents);
+        let unchanged = self.previous.files.get(&key) == Some(&hash) && path.exists();
+        {
+            let mut progress = self.progress.lock().unwrap();
+            progress.current.files.insert(key, hash);
+            if unchanged {
+                progress.summary.unchanged += 1;
+                return Ok(());
+            }
+            progress.summary.written += 1;
+        }
+        // the lock is not held while writing, so that the pages can be written in parallel
+        std::fs::write(path, contents)
+    }
+
+    /// Copies `source` into `dest` unless the previous build copied the same contents there.
+    /// A file copied once in a build is not copied again, since an image is copied each time it is rendered.
+    /// # Errors
+    /// Fails if the file I/O fails.
+    /// # Panics
+    /// Panics if another thread panicked while writing a file.
+    pub fn copy(&self, source: &Path, dest: &Path) -> std::io::Result<()> {
+        let key = self.key(dest);
+        if self
+            .progress
+            .lock()
+            .unwrap()
+            .current
+            .files
+            .contains_key(&key)
+        {
+            return Ok(());
+        }
+        self.write(dest, std::fs::read(source)?)
+    }
+
+    /// Keeps the files written by the previous build outside `dirs` in the manifest, as if this build had written them.
+    ///
+    /// A build that reruns only some of the generators calls this with the folders of those generators,
+    /// so that the files of the other generators are neither removed nor rewritten by the next build.
+    /// # Panics
+    /// Panics if another thread panicked while writing a file.
+    pub fn keep_previous_except(&self, dirs: &[PathBuf]) {
+        let mut progress = self.progress.lock().unwrap();
+        for (key, hash) in &self.previous.files {
+            if !dirs.iter().any(|dir| self.docs.join(key).starts_with(dir)) {
+                progress
+                    .current
+                    .files
+                    .entry(key.clone())
+                    .or_insert_with(|| hash.clone());
+            }
+        }
+    }
+
+    /// Removes the files in `dirs` that this build did not write, such as the pages of the phrases deleted from the input,
+    /// and saves the manifest for the next build.
+    /// # Errors
+    /// Fails if the file I/O fails.
+    /// # Panics
+    /// Panics if another thread panicked while writing a file.
+    pub fn finish(self, dirs: &[PathBuf]) -> anyhow::Result<Summary> {
+        let Progress {
+            current,
+            mut summary,
+        } = self.progress.into_inner().unwrap();
+        for dir in dirs {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                let key = path
+                    .strip_prefix(&self.docs)
+                    .unwrap_or(&path)
+                    .to_string_lossy();
+                if path.is_file() && !current.files.contains_key(&*key) {
+                    std::fs::remove_file(&path)?;
+                    summary.removed += 1;
+                }
+            }
+        }
+        std::fs::write(
+            self.docs.join(FILE_NAME),
+            serde_json::to_string_pretty(&current)? + "\n",
+        )?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Build, FILE_NAME};
+    use std::path::PathBuf;
+
+    /// An empty folder in the system's temporary folder
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spoonfed_pekzep_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("phrase")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn partial_build_keeps_the_other_files() {
+        let docs = empty_dir("partial_build");
+        let dirs = [docs.join("phrase")];
+        let phrase_dir = &dirs[0];
+        let build = Build::begin(&docs).unwrap();
+        for path in ["index.html", "phrase/a.html", "phrase/b.html"] {
+            build.write(&docs.join(path), path).unwrap();
+        }
+        build.finish(&dirs).unwrap();
+
+        let build = Build::begin(&docs).unwrap();
+        build.keep_previous_except(&dirs);
+        build
+            .write(&phrase_dir.join("a.html"), "phrase/a.html")
+            .unwrap();
+        let summary = build.finish(&dirs).unwrap();
+        assert_eq!(
+            (summary.written, summary.unchanged, summary.removed),
+            (0, 1, 1)
+        );
+        assert!(docs.join("index.html").exists());
+        assert!(!phrase_dir.join("b.html").exists());
+
+        // index.html is still known to be up to date
+        let build = Build::begin(&docs).unwrap();
+        build.write(&docs.join("index.html"), "index.html").unwrap();
+        assert_eq!(build.finish(&[]).unwrap().unchanged, 1);
+    }
+
+    #[test]
+    fn unchanged_files_are_not_written_again() {
+        let docs = empty_dir("unchanged_files");
+        let build = Build::begin(&docs).unwrap();
+        build.write(&docs.join("a.html"), "a").unwrap();
+        build.write(&docs.join("b.html"), "b").unwrap();
+        assert_eq!(build.finish(&[]).unwrap().written, 2);
+
+        let build = Build::begin(&docs).unwrap();
+        build.write(&docs.join("a.html"), "a").unwrap();
+        build.write(&docs.join("b.html"), "b, edited").unwrap();
+        let summary = build.finish(&[]).unwrap();
+        assert_eq!((summary.written, summary.unchanged), (1, 1));
+        assert_eq!(
+            std::fs::read_to_string(docs.join("b.html")).unwrap(),
+            "b, edited"
+        );
+    }
+
+    #[test]
+    fn file_deleted_by_hand_is_written_again() {
+        let docs = empty_dir("file_deleted_by_hand");
+        let build = Build::begin(&docs).unwrap();
+        build.write(&docs.join("a.html"), "a").unwrap();
+        build.finish(&[]).unwrap();
+
+        std::fs::remove_file(docs.join("a.html")).unwrap();
+        let build = Build::begin(&docs).unwrap();
+        build.write(&docs.join("a.html"), "a").unwrap();
+        assert_eq!(build.finish(&[]).unwrap().written, 1);
+        assert!(docs.join("a.html").exists());
+    }
+
+    #[test]
+    fn only_the_stale_files_in_the_given_folders_are_removed() {
+        let docs = empty_dir("stale_files");
+        let phrase_dir = docs.join("phrase");
+        for path in ["phrase/stale.html", "stale.html"] {
+            std::fs::write(docs.join(path), "").unwrap();
+        }
+        let build = Build::begin(&docs).unwrap();
+        build.write(&phrase_dir.join("a.html"), "a").unwrap();
+        let summary = build.finish(std::slice::from_ref(&phrase_dir)).unwrap();
+        assert_eq!(summary.removed, 1);
+        assert!(!phrase_dir.join("stale.html").exists());
+        assert!(phrase_dir.join("a.html").exists());
+        assert!(docs.join("stale.html").exists());
+    }
+
+    #[test]
+    fn file_is_copied_once_per_build() {
+        let docs = empty_dir("copied_once");
+        let source = docs.join("source.png");
+        std::fs::write(&source, "png").unwrap();
+        let build = Build::begin(&docs).unwrap();
+        for _ in 0..3 {
+            build.copy(&source, &docs.join("copy.png")).unwrap();
+        }
+        let summary = build.finish(&[]).unwrap();
+        assert_eq!((summary.written, summary.unchanged), (1, 0));
+        assert_eq!(
+            std::fs::read_to_string(docs.join("copy.png")).unwrap(),
+            "png"
+        );
+    }
+
+    #[test]
+    fn manifest_is_removed_until_the_build_finishes() {
+        let docs = empty_dir("manifest_removed");
+        Build::begin(&docs).unwrap().finish(&[]).unwrap();
+        assert!(docs.join(FILE_NAME).exists());
+
+        // a build that fails halfway never reaches `finish`
+        let build = Build::begin(&docs).unwrap();
+        assert!(!docs.join(FILE_NAME).exists());
+        build.finish(&[]).unwrap();
+        assert!(docs.join(FILE_NAME).exists());
+    }
+}
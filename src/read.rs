@@ -12,3 +12,12 @@ pub mod vocab;
 
 /// Reads "raw/linzklar-dismantling.tsv"
 pub mod linzklar_dismantling;
+
+/// Reads "raw/lint-keywords.tsv"
+pub mod lint_keyword;
+
+/// Reads "raw/lint-suppressions.tsv"
+pub mod lint_suppression;
+
+/// Reads "raw/lessons.tsv"
+pub mod lesson;
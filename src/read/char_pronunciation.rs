@@ -3,6 +3,7 @@ use pekzep_syllable::PekZepSyllable;
 use serde_derive::Deserialize as De;
 use std::collections::HashMap;
 use std::fs::File;
+use std::path::Path;
 use std::str::FromStr;
 
 #[derive(Debug, De)]
@@ -18,7 +19,7 @@ impl std::fmt::Display for Linzklar {
     }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct LinzklarString(pub Vec<Linzklar>);
 
 impl LinzklarString {
@@ -42,7 +43,7 @@ impl std::fmt::Display for LinzklarString {
     }
 }
 
-#[derive(Clone, Hash, PartialEq, Eq, Ord, PartialOrd, Copy)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Ord, PartialOrd, Copy)]
 pub struct Linzklar(char);
 impl Linzklar {
     #[must_use]
@@ -93,7 +94,7 @@ pub type CharSoundTable = Vec<(Linzklar, PekZepSyllable)>;
 pub type NonRecommendedCharTable = HashMap<Linzklar, Linzklar>;
 
 #[allow(clippy::tabs_in_doc_comments)]
-/// Parses "raw/字音.tsv" to obtain a table converting a character to a syllable,
+/// Parses "字音.tsv" (found at `path`) to obtain a table converting a character to a syllable,
 /// as well as a table converting a non-recommended character into a recommended alternative.
 ///
 /// The tsv used for the input should be of the following form:
@@ -110,10 +111,10 @@ pub type NonRecommendedCharTable = HashMap<Linzklar, Linzklar>;
 /// # Errors
 /// Gives errors if:
 /// - IO fails
-/// - "raw/字音.tsv" does not conform to an expected format
+/// - "字音.tsv" does not conform to an expected format
 /// - the Pekzep is unparsable
 ///
-pub fn parse() -> anyhow::Result<(CharSoundTable, NonRecommendedCharTable)> {
+pub fn parse(path: &Path) -> anyhow::Result<(CharSoundTable, NonRecommendedCharTable)> {
     fn convert(record: &Record) -> anyhow::Result<(Linzklar, PekZepSyllable)> {
         match PekZepSyllable::parse(&record.sound) {
             None => Err(anyhow!("Invalid sound {}", record.sound)),
@@ -121,7 +122,7 @@ pub fn parse() -> anyhow::Result<(CharSoundTable, NonRecommendedCharTable)> {
         }
     }
 
-    let f = File::open("raw/字音.tsv")?;
+    let f = File::open(path)?;
     let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(f);
     let mut ans = vec![];
     for result in rdr.deserialize() {
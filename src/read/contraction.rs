@@ -2,6 +2,7 @@ use anyhow::anyhow;
 use pekzep_syllable::PekZepSyllable;
 use serde_derive::Deserialize as De;
 use std::fs::File;
+use std::path::Path;
 
 use super::char_pronunciation::LinzklarString;
 
@@ -15,7 +16,7 @@ struct Record {
 pub type SoundTable = Vec<(LinzklarString, PekZepSyllable)>;
 
 #[allow(clippy::tabs_in_doc_comments)]
-/// Parses "raw/contraction.tsv" to obtain a table converting a string of characters to a contracted syllable.
+/// Parses "contraction.tsv" (found at `path`) to obtain a table converting a string of characters to a contracted syllable.
 /// The tsv used for the input should be of the following form:
 /// ```text
 ///characters	sound
@@ -25,10 +26,10 @@ pub type SoundTable = Vec<(LinzklarString, PekZepSyllable)>;
 /// # Errors
 /// Gives errors if:
 /// - IO fails
-/// - "raw/contraction.tsv" does not conform to an expected format
+/// - "contraction.tsv" does not conform to an expected format
 /// - the Pekzep is unparsable
 ///
-pub fn parse() -> anyhow::Result<SoundTable> {
+pub fn parse(path: &Path) -> anyhow::Result<SoundTable> {
     fn convert(record: &Record) -> anyhow::Result<(LinzklarString, PekZepSyllable)> {
         match PekZepSyllable::parse(&record.sound) {
             None => Err(anyhow!("Invalid sound {}", record.sound)),
@@ -36,7 +37,7 @@ pub fn parse() -> anyhow::Result<SoundTable> {
         }
     }
 
-    let f = File::open("raw/contraction.tsv")?;
+    let f = File::open(path)?;
     let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(f);
     let mut ans = vec![];
     for result in rdr.deserialize() {
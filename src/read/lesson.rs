@@ -0,0 +1,143 @@
+use serde_derive::Deserialize as De;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug, De)]
+struct Record {
+    title_en: String,
+    title_zh: String,
+    title_ja: String,
+    phrases: String,
+}
+
+/// The phrases from `first` to `last` (inclusive) in the order of the spreadsheet, each written as in `syllables_to_str_underscore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhraseRange {
+    pub first: String,
+    pub last: String,
+}
+
+impl PhraseRange {
+    /// Parses either a single phrase (`kait_kia1`) or a range (`kait_kia1..pai2_hia1_mok1`).
+    /// ```
+    /// use spoonfed_pekzep::read::lesson::PhraseRange;
+    /// let range = PhraseRange::new(" kait_kia1..pai2_hia1_mok1 ");
+    /// assert_eq!((range.first.as_str(), range.last.as_str()), ("kait_kia1", "pai2_hia1_mok1"));
+    /// assert_eq!(PhraseRange::new("mok1").last, "mok1");
+    /// ```
+    #[must_use]
+    pub fn new(s: &str) -> Self {
+        let s = s.trim();
+        let (first, last) = s.split_once("..").unwrap_or((s, s));
+        Self {
+            first: first.trim().to_owned(),
+            last: last.trim().to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for PhraseRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}..{}", self.first, self.last)
+        }
+    }
+}
+
+/// Parses the comma-separated phrases and ranges of the `phrases` column, ignoring the empty ones
+fn phrase_ranges(s: &str) -> Vec<PhraseRange> {
+    s.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(PhraseRange::new)
+        .collect()
+}
+
+/// A lesson, which groups the phrases that are to be learned together
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub title_en: String,
+    pub title_zh: String,
+    pub title_ja: String,
+    pub phrases: Vec<PhraseRange>,
+
+    /// 1-based line number in the tsv
+    pub line: usize,
+}
+
+#[allow(clippy::tabs_in_doc_comments)]
+/// Parses "lessons.tsv" (found at `path`), one lesson per line.
+/// The tsv used for the input should be of the following form:
+/// ```text
+///title_en	title_zh	title_ja	phrases
+///Greetings	问候	挨拶	kait_kia1, mua2_zep1_nan2..pai2_hia1_mok1
+/// ```
+/// Returns no lesson if the file does not exist, since defining the lessons is optional.
+/// # Errors
+/// Gives errors if:
+/// - IO fails
+/// - "lessons.tsv" does not conform to an expected format
+///
+pub fn parse(path: &Path) -> anyhow::Result<Vec<Item>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let f = File::open(path)?;
+    let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(f);
+    let headers = rdr.headers()?.clone();
+    let mut ans = vec![];
+    for result in rdr.records() {
+        let record = result?;
+        let line = record
+            .position()
+            .map_or(0, csv::Position::line)
+            .try_into()?;
+        let Record {
+            title_en,
+            title_zh,
+            title_ja,
+            phrases,
+        } = record.deserialize(Some(&headers))?;
+        ans.push(Item {
+            title_en,
+            title_zh,
+            title_ja,
+            phrases: phrase_ranges(&phrases),
+            line,
+        });
+    }
+    Ok(ans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PhraseRange, phrase_ranges};
+
+    fn range(first: &str, last: &str) -> PhraseRange {
+        PhraseRange {
+            first: first.to_owned(),
+            last: last.to_owned(),
+        }
+    }
+
+    #[test]
+    fn phrases_and_ranges_are_listed() {
+        assert_eq!(
+            phrase_ranges("kait_kia1, mua2_zep1_nan2 .. pai2_hia1_mok1,,"),
+            [
+                range("kait_kia1", "kait_kia1"),
+                range("mua2_zep1_nan2", "pai2_hia1_mok1")
+            ]
+        );
+        assert_eq!(phrase_ranges(" "), []);
+    }
+
+    #[test]
+    fn range_is_displayed_as_written() {
+        for s in ["kait_kia1", "kait_kia1..pai2_hia1_mok1"] {
+            assert_eq!(PhraseRange::new(s).to_string(), s);
+        }
+        assert_eq!(PhraseRange::new("mok1..mok1").to_string(), "mok1");
+    }
+}
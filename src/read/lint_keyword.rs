@@ -0,0 +1,55 @@
+use super::char_pronunciation::Linzklar;
+use serde_derive::Deserialize as De;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug, De)]
+struct Record {
+    rule: String,
+    linzklar: String,
+    keywords: String,
+}
+
+/// A linzklar whose presence requires one of the keywords to appear in the English translation
+#[derive(Clone)]
+pub struct Item {
+    /// the rule id, such as `kan1`
+    pub rule: String,
+    pub linzklar: Linzklar,
+    pub keywords: Vec<String>,
+}
+
+#[allow(clippy::tabs_in_doc_comments)]
+/// Parses "lint-keywords.tsv" (found at `path`).
+/// The tsv used for the input should be of the following form:
+/// ```text
+///rule	linzklar	keywords
+///kan1	躍	jump,dance
+///co1	壁	wall
+/// ```
+/// The keywords are separated by commas.
+/// # Errors
+/// Gives errors if:
+/// - IO fails
+/// - "lint-keywords.tsv" does not conform to an expected format
+/// - the second column is not a single linzklar
+///
+pub fn parse(path: &Path) -> anyhow::Result<Vec<Item>> {
+    let f = File::open(path)?;
+    let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(f);
+    let mut ans = vec![];
+    for result in rdr.deserialize() {
+        let record: Record = result?;
+        ans.push(Item {
+            linzklar: record.linzklar.parse()?,
+            keywords: record
+                .keywords
+                .split(',')
+                .map(|k| k.trim().to_owned())
+                .filter(|k| !k.is_empty())
+                .collect(),
+            rule: record.rule,
+        });
+    }
+    Ok(ans)
+}
@@ -0,0 +1,72 @@
+use serde_derive::Deserialize as De;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug, De)]
+struct Record {
+    phrase: String,
+    rule: String,
+    reason: String,
+}
+
+/// A known-good exception to a lint rule
+#[derive(Debug, Clone)]
+pub struct Item {
+    /// the phrase, written as in `syllables_to_str_underscore` (e.g. `mua2_zep1_nan2`)
+    pub phrase: String,
+
+    /// the rule id, such as `kan1`
+    pub rule: String,
+
+    /// why the exception is fine
+    pub reason: String,
+
+    /// 1-based line number in the tsv
+    pub line: usize,
+}
+
+#[allow(clippy::tabs_in_doc_comments)]
+/// Parses "lint-suppressions.tsv" (found at `path`).
+/// The tsv used for the input should be of the following form:
+/// ```text
+///phrase	rule	reason
+///pai2_kan1_ka1_a	kan1	figurative; "leap" in English
+/// ```
+/// The `reason` column is optional.
+/// # Errors
+/// Gives errors if:
+/// - IO fails
+/// - "lint-suppressions.tsv" does not conform to an expected format
+///
+pub fn parse(path: &Path) -> anyhow::Result<Vec<Item>> {
+    let f = File::open(path)?;
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(f);
+    let headers = rdr.headers()?.clone();
+    let mut ans = vec![];
+    for result in rdr.records() {
+        let mut record = result?;
+        // the `reason` column may be omitted
+        while record.len() < headers.len() {
+            record.push_field("");
+        }
+        let line = record
+            .position()
+            .map_or(0, csv::Position::line)
+            .try_into()?;
+        let Record {
+            phrase,
+            rule,
+            reason,
+        } = record.deserialize(Some(&headers))?;
+        ans.push(Item {
+            phrase,
+            rule,
+            reason,
+            line,
+        });
+    }
+    Ok(ans)
+}
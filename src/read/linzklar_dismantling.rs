@@ -1,6 +1,6 @@
 use anyhow::anyhow;
 use serde_derive::Deserialize as De;
-use std::{collections::HashMap, fs::File, str::FromStr};
+use std::{collections::HashMap, fs::File, path::Path, str::FromStr};
 
 use super::char_pronunciation::Linzklar;
 
@@ -16,18 +16,9 @@ pub type DismantlingTable = HashMap<Linzklar, DismantlingTree>;
 #[derive(Clone, Eq, PartialEq)]
 pub enum DismantlingTree {
     Leaf(Linzklar),
-    Unary(CustomUnaryOperator, Box<DismantlingTree>),
-    Binary(
-        IdsBinaryOperator,
-        Box<DismantlingTree>,
-        Box<DismantlingTree>,
-    ),
-    Trinary(
-        IdsTrinaryOperator,
-        Box<DismantlingTree>,
-        Box<DismantlingTree>,
-        Box<DismantlingTree>,
-    ),
+    Unary(CustomUnaryOperator, Box<Self>),
+    Binary(IdsBinaryOperator, Box<Self>, Box<Self>),
+    Trinary(IdsTrinaryOperator, Box<Self>, Box<Self>, Box<Self>),
 }
 
 impl DismantlingTree {
@@ -104,6 +95,43 @@ impl DismantlingTree {
     }
 }
 
+/// The linzklars `linzklar` is built from, found by dismantling it repeatedly, in the order they first appear.
+/// `linzklar` itself is not included.
+#[must_use]
+pub fn components(table: &DismantlingTable, linzklar: Linzklar) -> Vec<Linzklar> {
+    fn walk(table: &DismantlingTable, tree: &DismantlingTree, ans: &mut Vec<Linzklar>) {
+        match tree {
+            DismantlingTree::Leaf(c) => {
+                if ans.contains(c) {
+                    return;
+                }
+                ans.push(*c);
+                // a linzklar that cannot be dismantled is its own leaf, which returns immediately
+                if let Some(subtree) = table.get(c) {
+                    walk(table, subtree, ans);
+                }
+            }
+            DismantlingTree::Unary(_, d1) => walk(table, d1, ans),
+            DismantlingTree::Binary(_, d1, d2) => {
+                walk(table, d1, ans);
+                walk(table, d2, ans);
+            }
+            DismantlingTree::Trinary(_, d1, d2, d3) => {
+                walk(table, d1, ans);
+                walk(table, d2, ans);
+                walk(table, d3, ans);
+            }
+        }
+    }
+
+    let mut ans = vec![linzklar];
+    if let Some(tree) = table.get(&linzklar) {
+        walk(table, tree, &mut ans);
+    }
+    ans.remove(0);
+    ans
+}
+
 #[derive(Clone, Eq, PartialEq, Copy)]
 pub enum CustomUnaryOperator {
     Rotation,
@@ -132,7 +160,7 @@ pub enum IdsTrinaryOperator {
 }
 
 #[allow(clippy::tabs_in_doc_comments)]
-/// Parses "raw/linzklar-dismantling.tsv" to obtain a table converting a linzklar to how it should be dismantled.
+/// Parses "linzklar-dismantling.tsv" (found at `path`) to obtain a table converting a linzklar to how it should be dismantled.
 /// The tsv used for the input should be of the following form:
 /// ```text
 /// linzklar	dismantling
@@ -151,10 +179,10 @@ pub enum IdsTrinaryOperator {
 /// # Errors
 /// Gives errors if:
 /// - IO fails
-/// - "raw/linzklar-dismantling.tsv" does not conform to an expected format
+/// - "linzklar-dismantling.tsv" does not conform to an expected format
 /// - the IDS Sequence is unparsable
 ///
-pub fn parse() -> anyhow::Result<DismantlingTable> {
+pub fn parse(path: &Path) -> anyhow::Result<DismantlingTable> {
     fn convert(record: &Record) -> anyhow::Result<(Linzklar, DismantlingTree)> {
         let linzklar = Linzklar::from_str(&record.linzklar)?;
         if record.dismantling == "FALSE" {
@@ -167,7 +195,7 @@ pub fn parse() -> anyhow::Result<DismantlingTable> {
         }
     }
 
-    let f = File::open("raw/linzklar-dismantling.tsv")?;
+    let f = File::open(path)?;
     let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(f);
     let mut ans = vec![];
     for result in rdr.deserialize() {
@@ -177,3 +205,26 @@ pub fn parse() -> anyhow::Result<DismantlingTable> {
 
     ans.iter().map(convert).collect::<anyhow::Result<_>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DismantlingTable, DismantlingTree, components};
+    use crate::read::char_pronunciation::Linzklar;
+
+    fn linzklar(c: char) -> Linzklar {
+        Linzklar::from_char(c).unwrap()
+    }
+
+    #[test]
+    fn components_are_found_recursively_and_listed_once() {
+        let table: DismantlingTable = [('我', "⿰人己"), ('己', "⿱口人")]
+            .into_iter()
+            .map(|(c, ids)| (linzklar(c), DismantlingTree::parse(ids).unwrap()))
+            .collect();
+        assert_eq!(
+            components(&table, linzklar('我')),
+            [linzklar('人'), linzklar('己'), linzklar('口')]
+        );
+        assert!(components(&table, linzklar('人')).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::diagnostic::{Cell, Diagnostic, Diagnostics};
 use crate::read::vocab::InternalKeyGloss;
 use anyhow::anyhow;
 use csv::StringRecord;
@@ -9,6 +10,7 @@ use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::*;
+use std::path::Path;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Copy)]
 pub enum ExtSyllable {
@@ -80,6 +82,12 @@ pub struct Item {
     pub filetype: HashSet<FilePathType>,
     pub recording_author: Option<Author>,
     pub japanese: String,
+
+    /// whether the phrase still has a part to be substituted, which keeps it out of `raw.tsv` and `raw.js`
+    pub requires_substitution: bool,
+
+    /// 1-based line number in the tsv
+    pub line: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -105,32 +113,59 @@ impl std::fmt::Display for Author {
     }
 }
 
-fn encode_to_pekzep_syllables(i: &str) -> anyhow::Result<Vec<ExtSyllable>> {
+/// Splits a Latin transcription into syllables, ignoring the punctuation.
+/// ```
+/// use spoonfed_pekzep::read::phrase::{encode_to_pekzep_syllables, syllables_to_str_underscore};
+/// let syllables = encode_to_pekzep_syllables("xizi a. mua2 zep1?").unwrap();
+/// assert_eq!(syllables_to_str_underscore(&syllables), "xizi_a_mua2_zep1");
+/// ```
+/// # Errors
+/// Fails if any of the words is neither a valid Pekzep syllable nor `xizi`.
+pub fn encode_to_pekzep_syllables(i: &str) -> anyhow::Result<Vec<ExtSyllable>> {
     collect_any_errors(
-        i.split(|c: char| c.is_ascii_punctuation() || c.is_whitespace())
-            .filter_map(|k| {
-                if k.is_empty() {
-                    None
-                } else {
-                    Some(PekZepSyllable::parse(k).map_or_else(
-                        || {
-                            if k == "xizi" {
-                                Ok(ExtSyllable::Xizi)
-                            } else {
-                                Err(format!("Failed to parse a pekzep syllable {k}"))
-                            }
-                        },
-                        |s| Ok(ExtSyllable::Syllable(s)),
-                    ))
-                }
+        latin_words(i)
+            .into_iter()
+            .map(|(_, k)| {
+                PekZepSyllable::parse(k).map_or_else(
+                    || {
+                        if k == "xizi" {
+                            Ok(ExtSyllable::Xizi)
+                        } else {
+                            Err(format!("Failed to parse a pekzep syllable {k}"))
+                        }
+                    },
+                    |s| Ok(ExtSyllable::Syllable(s)),
+                )
             })
             .collect::<Vec<_>>(),
     )
     .map_err(|e| anyhow!(e.join("\n")))
 }
 
+/// Splits the latin transcription into the words that [`encode_to_pekzep_syllables`] reads, each with its byte range.
+/// ```
+/// use spoonfed_pekzep::read::phrase::latin_words;
+/// assert_eq!(latin_words("Xizi a. mua2?"), [(0..4, "Xizi"), (5..6, "a"), (8..12, "mua2")]);
+/// ```
+#[must_use]
+pub fn latin_words(i: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut ans = vec![];
+    let mut start = None;
+    for (idx, c) in i.char_indices().chain(std::iter::once((i.len(), ' '))) {
+        if c.is_ascii_punctuation() || c.is_whitespace() {
+            if let Some(start) = start.take() {
+                ans.push((start..idx, &i[start..idx]));
+            }
+        } else if start.is_none() {
+            start = Some(idx);
+        }
+    }
+    ans
+}
+
 #[allow(clippy::tabs_in_doc_comments)]
-/// Parses "raw/Spoonfed Pekzep - SpoonfedPekzep.tsv" to obtain a table converting a string of characters to a contracted syllable.
+/// Parses "Spoonfed Pekzep - SpoonfedPekzep.tsv" (found at `path`) to obtain a table converting a string of characters to a contracted syllable.
+///
 /// The tsv used for the input should be of the following form:
 /// ```text
 ///Hello / how are you	kait kia1!	善日！	Nǐ hǎo!	你好！	善日	wav	falira.lyjotafis		Jeemusn!
@@ -143,63 +178,25 @@ fn encode_to_pekzep_syllables(i: &str) -> anyhow::Result<Vec<ExtSyllable>> {
 /// - there is a duplication in phrases
 /// - filetype is invalid
 ///
-pub fn parse() -> anyhow::Result<LinkedHashMap<Vec<ExtSyllable>, Item>> {
+pub fn parse(path: &Path) -> anyhow::Result<LinkedHashMap<Vec<ExtSyllable>, Item>> {
     use log::info;
-    let f = File::open("raw/Spoonfed Pekzep - SpoonfedPekzep.tsv")?;
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    let mut rows = LinkedHashMap::new();
+    let mut rows: LinkedHashMap<Vec<ExtSyllable>, Item> = LinkedHashMap::new();
     let mut errors = vec![];
-    for line in f.lines() {
-        // to prevent double quotes from vanishing, I do not read with CSV parser
-        let rec: Record =
-            StringRecord::from(line?.split('\t').collect::<Vec<_>>()).deserialize(None)?;
-
-        info!("Parsing `{}`, `{}`:", rec.english, rec.pekzep_latin);
-        let decomposed = if rec.decomposed.is_empty() {
-            vec![]
-        } else {
-            let sentences = rec.decomposed.split("..");
-            let mut ans = vec![];
-            for s in sentences {
-                ans.push(SentenceGloss(
-                    s.split('.')
-                        .map(InternalKeyGloss::new)
-                        .collect::<anyhow::Result<_>>()?,
+    for (i, line) in f.lines().enumerate() {
+        let line_number = i + 1;
+        let row = match parse_row(&line?, line_number) {
+            Ok(row) => row,
+            Err(e) => {
+                errors.push(Diagnostic::error(
+                    "malformed-row",
+                    path,
+                    line_number,
+                    e.to_string(),
                 ));
+                continue;
             }
-            ans
-        };
-        let row = Item {
-            pekzep_latin: rec.pekzep_latin,
-            pekzep_hanzi: rec.pekzep_hanzi,
-            chinese_hanzi: rec.chinese_hanzi,
-            chinese_pinyin: rec.chinese_pinyin,
-            english: rec.english,
-            japanese: rec.japanese,
-            filetype: if rec.filetype.is_empty() {
-                HashSet::new()
-            } else {
-                let filetypes = rec.filetype.split(',').collect::<Vec<_>>();
-                let mut ans = HashSet::new();
-                for x in filetypes {
-                    ans.insert(match x.trim() {
-                        "wav" => FilePathType::Wav,
-                        "oga" => FilePathType::Oga,
-                        a => return Err(anyhow!("Invalid file type `{}`. Run with RUST_LOG environment variable set to `info` to see the details.", a)),
-                    });
-                }
-                ans
-            },
-            recording_author: if rec.recording_author == "jekto.vatimeliju" {
-                Some(Author::JektoVatimeliju)
-            } else if rec.recording_author == "falira.lyjotafis" {
-                Some(Author::FaliraLyjotafis)
-            } else if rec.recording_author.is_empty() {
-                None
-            } else {
-                Some(Author::Other(rec.recording_author))
-            },
-            decomposed,
         };
 
         // 未査読の行は飛ばす
@@ -208,20 +205,97 @@ pub fn parse() -> anyhow::Result<LinkedHashMap<Vec<ExtSyllable>, Item>> {
             continue;
         }
 
-        let syllables = encode_to_pekzep_syllables(&row.pekzep_latin)?;
-        if !syllables.is_empty() && rows.insert(syllables.clone(), row.clone()).is_some() {
-            // in HashSet::insert, if the set did have this value present, false is returned.
-            errors.push(format!(
-                "duplicate phrase detected: {}",
-                syllables_to_str_underscore(&syllables)
-            ));
+        let syllables = match encode_to_pekzep_syllables(&row.pekzep_latin) {
+            Ok(syllables) => syllables,
+            Err(e) => {
+                errors.push(
+                    Diagnostic::error("invalid-latin", path, line_number, e.to_string())
+                        .with_cell(Cell::whole("pekzep_latin", &row.pekzep_latin)),
+                );
+                continue;
+            }
+        };
+        if syllables.is_empty() {
+            continue;
+        }
+        if let Some(earlier) = rows.get(&syllables) {
+            errors.push(
+                Diagnostic::error(
+                    "duplicate-phrase",
+                    path,
+                    line_number,
+                    format!(
+                        "duplicate phrase detected: {} (already found at line {})",
+                        syllables_to_str_underscore(&syllables),
+                        earlier.line
+                    ),
+                )
+                .with_cell(Cell::whole("pekzep_latin", &row.pekzep_latin)),
+            );
+        } else {
+            rows.insert(syllables, row);
         }
     }
 
     if errors.is_empty() {
         Ok(rows)
     } else {
-        let err = errors.join("\n");
-        Err(anyhow!(err))
+        Err(Diagnostics(errors).into())
     }
 }
+
+fn parse_row(line: &str, line_number: usize) -> anyhow::Result<Item> {
+    use log::info;
+    // to prevent double quotes from vanishing, I do not read with CSV parser
+    let rec: Record = StringRecord::from(line.split('\t').collect::<Vec<_>>()).deserialize(None)?;
+
+    info!("Parsing `{}`, `{}`:", rec.english, rec.pekzep_latin);
+    let decomposed = if rec.decomposed.is_empty() {
+        vec![]
+    } else {
+        let sentences = rec.decomposed.split("..");
+        let mut ans = vec![];
+        for s in sentences {
+            ans.push(SentenceGloss(
+                s.split('.')
+                    .map(InternalKeyGloss::new)
+                    .collect::<anyhow::Result<_>>()?,
+            ));
+        }
+        ans
+    };
+    Ok(Item {
+        pekzep_latin: rec.pekzep_latin,
+        pekzep_hanzi: rec.pekzep_hanzi,
+        chinese_hanzi: rec.chinese_hanzi,
+        chinese_pinyin: rec.chinese_pinyin,
+        english: rec.english,
+        japanese: rec.japanese,
+        filetype: if rec.filetype.is_empty() {
+            HashSet::new()
+        } else {
+            let filetypes = rec.filetype.split(',').collect::<Vec<_>>();
+            let mut ans = HashSet::new();
+            for x in filetypes {
+                ans.insert(match x.trim() {
+                    "wav" => FilePathType::Wav,
+                    "oga" => FilePathType::Oga,
+                    a => return Err(anyhow!("Invalid file type `{}`. Run with RUST_LOG environment variable set to `info` to see the details.", a)),
+                });
+            }
+            ans
+        },
+        recording_author: if rec.recording_author == "jekto.vatimeliju" {
+            Some(Author::JektoVatimeliju)
+        } else if rec.recording_author == "falira.lyjotafis" {
+            Some(Author::FaliraLyjotafis)
+        } else if rec.recording_author.is_empty() {
+            None
+        } else {
+            Some(Author::Other(rec.recording_author))
+        },
+        decomposed,
+        requires_substitution: !rec.requires_substitution.is_empty(),
+        line: line_number,
+    })
+}
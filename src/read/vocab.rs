@@ -1,3 +1,4 @@
+use crate::diagnostic::{Cell, Diagnostic, Diagnostics};
 use anyhow::anyhow;
 use csv::StringRecord;
 use serde_derive::{Deserialize as De, Serialize as Ser};
@@ -5,10 +6,11 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::*;
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+use std::path::Path;
 
 /// an enum used to talk about a splittable compound
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Ser)]
+#[serde(rename_all = "snake_case")]
 pub enum SplittableCompoundInfo {
     /// Denotes the former half of the splittable compound, such as `享#銭`
     FormerHalfHash,
@@ -35,22 +37,47 @@ pub struct Item {
     pub parts_of_speech: String,
     pub parts_of_speech_supplement: String,
     pub english_gloss: String,
+
+    /// 1-based line number in the tsv
+    pub line: usize,
 }
 
 impl Item {
-    pub fn to_tab_separated_with_custom_linzifier<F>(&self, f: F) -> String
+    /// # Errors
+    /// Fails if `f` fails.
+    pub fn to_tab_separated_with_custom_linzifier<F>(&self, f: F) -> anyhow::Result<String>
     where
-        F: FnOnce(&str) -> String,
+        F: FnOnce(&str) -> anyhow::Result<String>,
     {
-        format!(
+        Ok(format!(
             "{}\t{}\t<span style=\"filter:brightness(65%) contrast(500%);\">{}</span>\t{}\t{}\t{}",
             self.pekzep_latin,
             self.pekzep_hanzi,
-            f(&self.pekzep_hanzi),
+            f(&self.pekzep_hanzi)?,
             self.parts_of_speech,
             self.parts_of_speech_supplement,
             self.english_gloss
-        )
+        ))
+    }
+}
+
+#[cfg(test)]
+impl Item {
+    /// A word found at the line 2, for the tests of the modules using the words
+    pub(crate) fn for_test(
+        pekzep_latin: &str,
+        pekzep_hanzi: &str,
+        parts_of_speech: &str,
+        english_gloss: &str,
+    ) -> Self {
+        Self {
+            pekzep_latin: pekzep_latin.to_owned(),
+            pekzep_hanzi: pekzep_hanzi.to_owned(),
+            parts_of_speech: parts_of_speech.to_owned(),
+            parts_of_speech_supplement: String::new(),
+            english_gloss: english_gloss.to_owned(),
+            line: 2,
+        }
     }
 }
 
@@ -365,7 +392,7 @@ impl InternalKey {
 }
 
 #[allow(clippy::tabs_in_doc_comments)]
-/// Parses "raw/Spoonfed Pekzep - 語彙整理（超草案）.tsv" to make a lookup table from the `InternalKey` to the `Item`.
+/// Parses "Spoonfed Pekzep - 語彙整理（超草案）.tsv" (found at `path`) to make a lookup table from the `InternalKey` to the `Item`.
 /// The tsv used for the input should be of the following form:
 /// ```text
 ///善日	kait kia1	善日	interjection	greeting	hello
@@ -378,38 +405,72 @@ impl InternalKey {
 /// # Errors
 /// Gives errors if:
 /// - IO fails
-/// - "raw/Spoonfed Pekzep - 語彙整理（超草案）.tsv" does not conform to an expected format
+/// - "Spoonfed Pekzep - 語彙整理（超草案）.tsv" does not conform to an expected format
 ///
-pub fn parse() -> anyhow::Result<HashMap<InternalKey, Item>> {
-    let f = File::open("raw/Spoonfed Pekzep - 語彙整理（超草案）.tsv")?;
+pub fn parse(path: &Path) -> anyhow::Result<HashMap<InternalKey, Item>> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    let mut res = HashMap::new();
+    let mut res: HashMap<InternalKey, Item> = HashMap::new();
     let mut errors = vec![];
-    for line in f.lines() {
+    for (i, line) in f.lines().enumerate() {
+        let line_number = i + 1;
         // to prevent double quotes from vanishing, I do not read with CSV parser
         let row: Record =
-            StringRecord::from(line?.split('\t').collect::<Vec<_>>()).deserialize(None)?;
-        if !row.key.is_empty()
-            && res
-                .insert(
-                    InternalKey::new(&row.key)?,
-                    Item {
-                        pekzep_latin: row.pekzep_latin,
-                        pekzep_hanzi: row.pekzep_hanzi,
-                        parts_of_speech: row.parts_of_speech,
-                        parts_of_speech_supplement: row.parts_of_speech_supplement,
-                        english_gloss: row.english_gloss,
-                    },
+            match StringRecord::from(line?.split('\t').collect::<Vec<_>>()).deserialize(None) {
+                Ok(row) => row,
+                Err(e) => {
+                    errors.push(Diagnostic::error(
+                        "malformed-row",
+                        path,
+                        line_number,
+                        e.to_string(),
+                    ));
+                    continue;
+                }
+            };
+        if row.key.is_empty() {
+            continue;
+        }
+        let key = match InternalKey::new(&row.key) {
+            Ok(key) => key,
+            Err(e) => {
+                errors.push(
+                    Diagnostic::error("invalid-key", path, line_number, e.to_string())
+                        .with_cell(Cell::whole("key", &row.key)),
+                );
+                continue;
+            }
+        };
+        if let Some(earlier) = res.get(&key) {
+            errors.push(
+                Diagnostic::error(
+                    "duplicate-word",
+                    path,
+                    line_number,
+                    format!(
+                        "duplicate key detected: {} (already found at line {})",
+                        row.key, earlier.line
+                    ),
                 )
-                .is_some()
-        {
-            errors.push(format!("duplicate key detected: {}", row.key));
+                .with_cell(Cell::whole("key", &row.key)),
+            );
+        } else {
+            res.insert(
+                key,
+                Item {
+                    pekzep_latin: row.pekzep_latin,
+                    pekzep_hanzi: row.pekzep_hanzi,
+                    parts_of_speech: row.parts_of_speech,
+                    parts_of_speech_supplement: row.parts_of_speech_supplement,
+                    english_gloss: row.english_gloss,
+                    line: line_number,
+                },
+            );
         }
     }
     if errors.is_empty() {
         Ok(res)
     } else {
-        let err = errors.join("\n");
-        Err(anyhow!(err))
+        Err(Diagnostics(errors).into())
     }
 }
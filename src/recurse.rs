@@ -22,28 +22,28 @@ fn test_foo() {
         String::from(
             r#"                <a href="./酒.html" style="text-decoration: none;">
                     <div class="dismantling-bar">
-                        <span style="font-family: LinzklarRounded;">酒</span><span lang="ja">【酒】</span>
+                        <img src="../char_bitmap_img/酒.png" height="16" /><span lang="ja">【酒】</span>
                     </div>
                 </a>
                 <div class="dismantling-frame">
                     <div style="flex: 50%">
                         <a href="./奮.html" style="text-decoration: none;">
                             <div class="dismantling-bar">
-                                <span style="font-family: LinzklarRounded;">奮</span><span lang="ja">【奮】</span>
+                                <img src="../char_bitmap_img/奮.png" height="16" /><span lang="ja">【奮】</span>
                             </div>
                         </a>
                         <div class="dismantling-frame">
                             <div style="flex: 50%">
                                 <a href="./心.html" style="text-decoration: none;">
                                     <div class="dismantling-bar">
-                                        <span style="font-family: LinzklarRounded;">心</span><span lang="ja">【心】</span>
+                                        <img src="../char_bitmap_img/心.png" height="16" /><span lang="ja">【心】</span>
                                     </div>
                                 </a>
                             </div>
                             <div style="flex: 50%">
                                 <a href="./火.html" style="text-decoration: none;">
                                     <div class="dismantling-bar">
-                                        <span style="font-family: LinzklarRounded;">火</span><span lang="ja">【火】</span>
+                                        <img src="../char_bitmap_img/火.png" height="16" /><span lang="ja">【火】</span>
                                     </div>
                                 </a>
                             </div>
@@ -52,7 +52,7 @@ fn test_foo() {
                     <div style="flex: 50%">
                         <a href="./水.html" style="text-decoration: none;">
                             <div class="dismantling-bar">
-                                <span style="font-family: LinzklarRounded;">水</span><span lang="ja">【水】</span>
+                                <img src="../char_bitmap_img/水.png" height="16" /><span lang="ja">【水】</span>
                             </div>
                         </a>
                     </div>
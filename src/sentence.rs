@@ -0,0 +1,62 @@
+use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+use crate::read::phrase::ExtSyllable;
+use std::ops::Range;
+
+/// How a syllable is written in the hanzi transcription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyllableHanzi {
+    Linzklar(Linzklar),
+
+    /// characters read together as one syllable, such as «足手»
+    Contraction(LinzklarString),
+
+    Xizi,
+}
+
+impl std::fmt::Display for SyllableHanzi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linzklar(linzklar) => write!(f, "{linzklar}"),
+            Self::Contraction(contraction) => write!(f, "{contraction}"),
+            Self::Xizi => write!(f, "xizi"),
+        }
+    }
+}
+
+/// A syllable, as written in `pekzep_hanzi` and in `pekzep_latin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignedSyllable {
+    pub hanzi: SyllableHanzi,
+    pub syllable: ExtSyllable,
+
+    /// the word as written in `pekzep_latin`, which may differ from `syllable` in capitalization
+    pub latin: String,
+
+    /// the byte range in `pekzep_hanzi`
+    pub hanzi_span: Range<usize>,
+
+    /// the byte range in `pekzep_latin`
+    pub latin_span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    Syllable(AlignedSyllable),
+
+    /// punctuation or whitespace in `pekzep_hanzi`, such as `。` or `(`
+    Punctuation(String),
+}
+
+/// A phrase whose hanzi transcription, syllables and latin transcription are aligned with each other.
+/// The elements are in the order of `pekzep_hanzi`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PekzepSentence(pub Vec<Element>);
+
+impl PekzepSentence {
+    pub fn syllables(&self) -> impl Iterator<Item = &AlignedSyllable> {
+        self.0.iter().filter_map(|element| match element {
+            Element::Syllable(syllable) => Some(syllable),
+            Element::Punctuation(_) => None,
+        })
+    }
+}
@@ -0,0 +1,522 @@
+use askama::Template;
+
+use crate::askama_templates::DiagnosticsTemplate;
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Severity};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The page listing the diagnostics of the latest build
+pub const DIAGNOSTICS_PATH: &str = "/_diagnostics";
+
+/// Answers the number of the latest build, which the pages poll to reload themselves
+pub const BUILD_NUMBER_PATH: &str = "/_build";
+
+/// How many connections are answered at once; the others wait until a worker is free
+pub const WORKERS: usize = 4;
+
+/// How long a connection may stay idle
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of the latest build, shared between the watcher and the server
+#[derive(Debug, Clone, Default)]
+pub struct Status {
+    /// incremented at each build, successful or not
+    pub build_number: usize,
+
+    /// the errors and the warnings found in the input
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// a failure which is not about the input, such as an I/O error
+    pub failure: Option<String>,
+
+    /// whether a file in [`TEMPLATES_DIR`] has changed since the server started, which requires rebuilding the binary
+    pub templates_changed: bool,
+}
+
+impl Status {
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+}
+
+/// The folder askama compiled the templates from.
+///
+/// The templates are compiled into the binary, so a change in them cannot be rebuilt by `serve`;
+/// it is only noticed so that the pages can tell to rebuild the binary and restart.
+/// The folder is where the binary was built from, and a binary moved away from its source checkout finds nothing to watch.
+pub const TEMPLATES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/templates");
+
+/// A step of the build, which writes some of the output and can be rerun by itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Generator {
+    /// `phrase/`
+    Phrases,
+    /// `vocab/`
+    Vocabs,
+    /// `vocab_list_internal.html`
+    VocabListInternal,
+    /// `vocab_list.html`
+    VocabList,
+    /// `dictionary.html`
+    Dictionary,
+    /// `char/`
+    Chars,
+    /// `char_list.html`
+    CharList,
+    /// `index.html`
+    Index,
+    /// `lesson/`, both the lessons and their worksheets
+    Lessons,
+    /// `graded_index.html`
+    Graded,
+    /// `raw.tsv`
+    RawTsv,
+    /// `raw.js`
+    RawJs,
+    /// `corpus.json`
+    Corpus,
+    /// `cloze.json` and `cloze.html`
+    Cloze,
+    /// `quiz.html` and `quiz.js`
+    Quiz,
+    /// `char_count.js`
+    CharCountJs,
+    /// `interlinear.tex`
+    Interlinear,
+}
+
+impl Generator {
+    /// Every generator, in the order the build runs them
+    pub const ALL: [Self; 17] = [
+        Self::Phrases,
+        Self::Vocabs,
+        Self::VocabListInternal,
+        Self::VocabList,
+        Self::Dictionary,
+        Self::Chars,
+        Self::CharList,
+        Self::Index,
+        Self::Lessons,
+        Self::Graded,
+        Self::RawTsv,
+        Self::RawJs,
+        Self::Corpus,
+        Self::Cloze,
+        Self::Quiz,
+        Self::CharCountJs,
+        Self::Interlinear,
+    ];
+
+    /// The folder filled with one file per item, which only this generator writes into
+    #[must_use]
+    pub fn folder(self, config: &Config) -> Option<PathBuf> {
+        match self {
+            Self::Phrases => Some(config.output.phrase_dir()),
+            Self::Vocabs => Some(config.output.vocab_dir()),
+            Self::Chars => Some(config.output.char_dir()),
+            Self::Lessons => Some(config.output.lesson_dir()),
+            _ => None,
+        }
+    }
+}
+
+/// The generators that read any of `changed`, which are the paths found in [`snapshot`].
+///
+/// The verification always reruns, but a change in the dismantlings, the lessons or the lint settings only reruns the few generators reading them.
+/// Every other input is read into the data bundle or shown as an image on almost every page, so its change reruns all the generators.
+#[must_use]
+pub fn affected_generators(config: &Config, changed: &[PathBuf]) -> Vec<Generator> {
+    let source = &config.source;
+    let mut ans = BTreeSet::new();
+    for path in changed {
+        if *path == source.linzklar_dismantling {
+            ans.extend([Generator::Dictionary, Generator::Chars]);
+        } else if *path == source.lessons {
+            ans.extend([Generator::Index, Generator::Lessons]);
+        } else if *path == source.lint_keywords || *path == source.lint_suppressions {
+            // only the verification reads them
+        } else {
+            return Generator::ALL.to_vec();
+        }
+    }
+    ans.into_iter().collect()
+}
+
+/// The paths whose modification time differs between the snapshots, including those created or removed
+#[must_use]
+pub fn changed_paths(
+    before: &BTreeMap<PathBuf, SystemTime>,
+    after: &BTreeMap<PathBuf, SystemTime>,
+) -> Vec<PathBuf> {
+    let mut ans = before
+        .iter()
+        .filter(|(path, modified)| after.get(*path) != Some(*modified))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    ans.extend(
+        after
+            .keys()
+            .filter(|path| !before.contains_key(*path))
+            .cloned(),
+    );
+    ans
+}
+
+/// The files whose change triggers a rebuild: every input file and every image of a linzklar
+#[must_use]
+pub fn watched_inputs(config: &Config) -> Vec<PathBuf> {
+    let source = &config.source;
+    vec![
+        source.phrase.clone(),
+        source.vocab.clone(),
+        source.char_pronunciation.clone(),
+        source.contraction.clone(),
+        source.linzklar_dismantling.clone(),
+        source.lint_keywords.clone(),
+        source.lint_suppressions.clone(),
+        source.lessons.clone(),
+        source.char_img.clone(),
+        source.char_img_fallback.clone(),
+    ]
+}
+
+/// The modification time of each file in `paths`, looking into the directories recursively.
+/// A path that does not exist is left out, so that creating it counts as a change.
+#[must_use]
+pub fn snapshot(paths: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    fn visit(path: &Path, ans: &mut BTreeMap<PathBuf, SystemTime>) {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+                visit(&entry.path(), ans);
+            }
+        } else if let Ok(modified) = metadata.modified() {
+            ans.insert(path.to_owned(), modified);
+        }
+    }
+
+    let mut ans = BTreeMap::new();
+    for path in paths {
+        visit(path, &mut ans);
+    }
+    ans
+}
+
+/// Decodes `%E8%A8%80` into `言`. Returns `None` if the result is not UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut ans = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            ans.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            ans.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(ans).ok()
+}
+
+/// The file in `docs` requested by the path of a URL, such as `/char/%E8%A8%80.html` or `/` for `index.html`.
+/// Returns `None` for a path that tries to go outside `docs`.
+///
+/// ```
+/// use spoonfed_pekzep::serve::resolve;
+/// use std::path::Path;
+/// let docs = Path::new("docs");
+/// assert_eq!(resolve(docs, "/char/%E8%A8%80.html?x=1"), Some(docs.join("char/言.html")));
+/// assert_eq!(resolve(docs, "/"), Some(docs.join("index.html")));
+/// assert_eq!(resolve(docs, "/../raw/a.tsv"), None);
+/// ```
+#[must_use]
+pub fn resolve(docs: &Path, url_path: &str) -> Option<PathBuf> {
+    let url_path = url_path.split(['?', '#']).next().unwrap_or("");
+    let relative = PathBuf::from(percent_decode(url_path.trim_start_matches('/'))?);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    let path = docs.join(relative);
+    if path.is_dir() || url_path.ends_with('/') {
+        Some(path.join("index.html"))
+    } else {
+        Some(path)
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("css") => "text/css; charset=utf-8",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("wav") => "audio/wav",
+        Some("oga") => "audio/ogg",
+        Some("tsv" | "txt" | "tex") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Renders the page at [`DIAGNOSTICS_PATH`].
+/// # Errors
+/// Fails if the render fails.
+pub fn diagnostics_page(status: &Status) -> askama::Result<String> {
+    DiagnosticsTemplate {
+        build_number: status.build_number,
+        error_count: status.count(Severity::Error),
+        warning_count: status.count(Severity::Warning),
+        diagnostics: &status
+            .diagnostics
+            .iter()
+            .map(Diagnostic::render_human)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        failure: status.failure.as_deref().unwrap_or(""),
+        templates_changed: status.templates_changed,
+    }
+    .render()
+}
+
+/// A banner showing whether the latest build found anything, and a script reloading the page after each build
+fn overlay(status: &Status) -> String {
+    let errors = status.count(Severity::Error);
+    let warnings = status.count(Severity::Warning);
+    let banner = if errors > 0
+        || warnings > 0
+        || status.failure.is_some()
+        || status.templates_changed
+    {
+        format!(
+            r#"<div style="position: fixed; bottom: 0; right: 0; z-index: 1000; padding: 6px 12px; font: 14px sans-serif; background-color: {}; border-top-left-radius: 6px;"><a href="{DIAGNOSTICS_PATH}" style="color: black">{errors} error(s), {warnings} warning(s){}</a></div>"#,
+            if errors > 0 || status.failure.is_some() {
+                "#ffcdd2"
+            } else {
+                "#fff9c4"
+            },
+            if status.failure.is_some() {
+                ", build failed"
+            } else {
+                ""
+            }
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        r#"{banner}<script>setInterval(() => fetch("{BUILD_NUMBER_PATH}").then(r => r.text()).then(n => {{ if (n !== "{}") location.reload(); }}).catch(() => {{}}), 1000);</script>"#,
+        status.build_number
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    code: &str,
+    content_type: &str,
+    body: &[u8],
+    head: bool,
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {code}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    if !head {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// Answers a single request, serving the files in `docs` with the overlay inserted into every HTML page.
+///
+/// A client that stops sending or receiving for [`TIMEOUT`] is dropped, so that it does not hold a worker.
+/// # Errors
+/// Fails if the connection fails or times out.
+/// # Panics
+/// Panics if the watcher panicked while updating `status`.
+pub fn handle(
+    mut stream: TcpStream,
+    docs: &Path,
+    status: &Arc<Mutex<Status>>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+    // the headers are not used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, url_path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"",
+            head,
+        );
+    }
+
+    let status = status.lock().unwrap().clone();
+    if url_path == BUILD_NUMBER_PATH {
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/plain",
+            status.build_number.to_string().as_bytes(),
+            head,
+        );
+    }
+    if url_path == DIAGNOSTICS_PATH {
+        let page = diagnostics_page(&status).unwrap_or_else(|e| e.to_string());
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            page.as_bytes(),
+            head,
+        );
+    }
+
+    let Some(path) = resolve(docs, url_path) else {
+        return respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            b"forbidden",
+            head,
+        );
+    };
+    let Ok(mut body) = std::fs::read(&path) else {
+        return respond(
+            &mut stream,
+            "404 Not Found",
+            "text/plain",
+            b"not found",
+            head,
+        );
+    };
+    let content_type = content_type(&path);
+    if content_type.starts_with("text/html") {
+        let html = String::from_utf8_lossy(&body);
+        let pos = html.rfind("</body>").unwrap_or(html.len());
+        body = format!("{}{}{}", &html[..pos], overlay(&status), &html[pos..]).into_bytes();
+    }
+    respond(&mut stream, "200 OK", content_type, &body, head)
+}
+
+/// Answers the requests to `listener` in the background with [`WORKERS`] threads, each handling one connection at a time.
+/// # Errors
+/// Fails if the listener cannot be shared between the threads.
+pub fn spawn_workers(
+    listener: &TcpListener,
+    docs: &Path,
+    status: &Arc<Mutex<Status>>,
+) -> std::io::Result<()> {
+    for _ in 0..WORKERS {
+        let listener = listener.try_clone()?;
+        let (docs, status) = (docs.to_owned(), Arc::clone(status));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle(stream, &docs, &status) {
+                    log::info!("A connection failed: {e}");
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Generator, affected_generators, changed_paths};
+    use crate::config::Config;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn only_the_readers_of_the_changed_input_rerun() {
+        let config = Config::default();
+        let source = &config.source;
+        assert_eq!(
+            affected_generators(&config, std::slice::from_ref(&source.lessons)),
+            [Generator::Index, Generator::Lessons]
+        );
+        assert_eq!(
+            affected_generators(
+                &config,
+                &[source.linzklar_dismantling.clone(), source.lessons.clone()]
+            ),
+            [
+                Generator::Dictionary,
+                Generator::Chars,
+                Generator::Index,
+                Generator::Lessons
+            ]
+        );
+        assert_eq!(
+            affected_generators(&config, std::slice::from_ref(&source.lint_suppressions)),
+            []
+        );
+        assert_eq!(
+            affected_generators(
+                &config,
+                &[source.lessons.clone(), source.char_img.join("言.png")]
+            ),
+            Generator::ALL
+        );
+        assert_eq!(
+            affected_generators(&config, std::slice::from_ref(&source.phrase)),
+            Generator::ALL
+        );
+    }
+
+    #[test]
+    fn created_modified_and_removed_files_are_changed() {
+        let (t0, t1) = (
+            SystemTime::UNIX_EPOCH,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+        );
+        let before = BTreeMap::from([
+            (PathBuf::from("kept"), t0),
+            (PathBuf::from("modified"), t0),
+            (PathBuf::from("removed"), t0),
+        ]);
+        let after = BTreeMap::from([
+            (PathBuf::from("created"), t0),
+            (PathBuf::from("kept"), t0),
+            (PathBuf::from("modified"), t1),
+        ]);
+        assert_eq!(
+            changed_paths(&before, &after),
+            [
+                PathBuf::from("modified"),
+                PathBuf::from("removed"),
+                PathBuf::from("created")
+            ]
+        );
+    }
+}
@@ -1,138 +1,395 @@
+use crate::config::{GradedSettings, OutputPaths, SourcePaths};
+use crate::diagnostic::{Cell, Diagnostic, Diagnostics, Severity};
+use crate::graded::GradedOrder;
+use crate::hanzi::{self, HanziToken};
+use crate::index::Index;
+use crate::lint::{LintLevels, Registry, Suppressions};
 use crate::read;
 use crate::read::char_pronunciation::{Linzklar, LinzklarString};
 use crate::read::vocab::{InternalKey, SplittableCompoundInfo};
-use anyhow::anyhow;
+use crate::sentence::{AlignedSyllable, Element, PekzepSentence, SyllableHanzi};
+use anyhow::Context;
 use linked_hash_map::LinkedHashMap;
 use pekzep_syllable::PekZepSyllable;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
 
 pub struct Rows3Item {
     pub syllables: Vec<read::phrase::ExtSyllable>,
+
+    /// `row.pekzep_hanzi` aligned with `syllables` and `row.pekzep_latin`
+    pub sentence: PekzepSentence,
     pub decomposition: Vec<Vec<DecompositionItem>>,
     pub row: read::phrase::Item,
 }
 
+/// A lesson in "lessons.tsv", with its phrases looked up
+#[derive(Debug, Clone)]
+pub struct Lesson {
+    pub title_en: String,
+    pub title_zh: String,
+    pub title_ja: String,
+
+    /// the indices in `rows3`, in the order listed in "lessons.tsv"
+    pub phrases: Vec<usize>,
+}
+
 #[readonly::make]
 pub struct DataBundle {
     pub rows3: Vec<Rows3Item>,
     pub vocab_ordered: LinkedHashMap<InternalKey, read::vocab::Item>,
     pub vocab_count: HashMap<InternalKey, usize>,
     pub char_count: HashMap<Linzklar, usize>,
+
+    /// the phrases and the words looked up by the words, the linzklars and so on that they contain
+    pub index: Index,
+
+    /// `rows3` reordered for the beginners, computed on the first call to `graded`
+    graded: OnceLock<GradedOrder>,
+
+    /// empty if "lessons.tsv" does not exist
+    pub lessons: Vec<Lesson>,
+
+    /// the warnings found while verifying the input
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl DataBundle {
-    fn check_sentence_pronunciation(
+    /// `rows3` reordered for the beginners
+    pub fn graded(&self) -> &GradedOrder {
+        self.graded
+            .get_or_init(|| GradedOrder::new(&self.rows3, &self.vocab_count))
+    }
+
+    /// Reports the phrases introducing more than `settings.max_new_words` new words in the graded order as candidates for rewriting.
+    #[must_use]
+    pub fn check_graded(&self, phrase_path: &Path, settings: &GradedSettings) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        for step in &self.graded().steps {
+            if step.new_words.len() <= settings.max_new_words {
+                continue;
+            }
+            let row = &self.rows3[step.index].row;
+            let decomposed = row
+                .decomposed
+                .iter()
+                .map(read::phrase::SentenceGloss::to_debugtext)
+                .collect::<Vec<_>>()
+                .join("..");
+            diagnostics.push(
+                Diagnostic::warning(
+                    "too-many-new-words",
+                    phrase_path,
+                    row.line,
+                    format!(
+                        "`{}` introduces {} new words ({}) in the graded order, which is more than {}. Consider rewriting it.",
+                        read::phrase::syllables_to_str_underscore(&self.rows3[step.index].syllables),
+                        step.new_words.len(),
+                        step.new_words
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        settings.max_new_words
+                    ),
+                )
+                .with_cell(Cell::whole("decomposed", &decomposed)),
+            );
+        }
+        diagnostics
+    }
+
+    /// Reports the recordings in `output.sounds_dir()` that are listed in the `filetype` column but not found, or found but not listed.
+    #[must_use]
+    pub fn check_sounds(&self, phrase_path: &Path, output: &OutputPaths) -> Vec<Diagnostic> {
+        use read::phrase::FilePathType;
+        let mut diagnostics = vec![];
+        for Rows3Item { syllables, row, .. } in &self.rows3 {
+            let filename = read::phrase::syllables_to_str_underscore(syllables);
+            for (filetype, extension) in [(FilePathType::Oga, "oga"), (FilePathType::Wav, "wav")] {
+                let exists = output
+                    .sounds_dir()
+                    .join(format!("{filename}.{extension}"))
+                    .exists();
+                let (rule, message) = match (row.filetype.contains(&filetype), exists) {
+                    (true, false) => (
+                        format!("missing-{extension}"),
+                        format!("{extension} file not found: {filename}.{extension}"),
+                    ),
+                    (false, true) => (
+                        format!("unlinked-{extension}"),
+                        format!(
+                            "{extension} file IS found, but is not linked: {filename}.{extension}"
+                        ),
+                    ),
+                    _ => continue,
+                };
+                diagnostics.push(
+                    Diagnostic::warning(&rule, phrase_path, row.line, message)
+                        .with_cell(Cell::whole("pekzep_latin", &row.pekzep_latin)),
+                );
+            }
+        }
+        diagnostics
+    }
+
+    /// Aligns the hanzi transcription of each phrase with its syllables,
+    /// checking that the characters are pronounced as the syllables say.
+    fn align_sentences(
+        path: &Path,
         spoonfed_rows: &LinkedHashMap<Vec<read::phrase::ExtSyllable>, read::phrase::Item>,
         char_pronunciation: &[(Linzklar, pekzep_syllable::PekZepSyllable)],
         contraction_pronunciation: &[(LinzklarString, pekzep_syllable::PekZepSyllable)],
-    ) -> anyhow::Result<()> {
-        use log::info;
+    ) -> (
+        HashMap<Vec<read::phrase::ExtSyllable>, PekzepSentence>,
+        Vec<Diagnostic>,
+    ) {
         eprintln!(
             "Checking if the pronunciations of the sentences are correct. Run with RUST_LOG environment variable set to `info` to see the details."
         );
+        let mut sentences = HashMap::new();
+        let mut diagnostics = vec![];
         for (k, v) in spoonfed_rows {
-            let mut iter = v.pekzep_hanzi.chars();
-            let mut key_iter = k.iter();
-            while let Some(c) = iter.next() {
-                if c.is_whitespace() || c.is_ascii_punctuation() || "！？「」。".contains(c) {
-                    info!("Skipped: {c}");
-                } else if c == '«' {
-                    // Handle exceptional contractions such as «足手» xiop1
-                    let mut contraction = String::new();
-                    {
-                        let mut c = iter.next().expect("Unmatched guillemet");
-                        loop {
-                            if c == '»' {
-                                break;
-                            }
-                            contraction.push(c);
-                            c = iter.next().expect("Unmatched guillemet");
-                        }
-                    }
+            match Self::align_sentence_of_row(
+                k,
+                &v.pekzep_hanzi,
+                &v.pekzep_latin,
+                char_pronunciation,
+                contraction_pronunciation,
+            ) {
+                Ok(sentence) => {
+                    sentences.insert(k.clone(), sentence);
+                }
+                Err((range, message)) => diagnostics.push(
+                    Diagnostic::error("sentence-pronunciation", path, v.line, message)
+                        .with_cell(Cell::new("pekzep_hanzi", &v.pekzep_hanzi, range)),
+                ),
+            }
+        }
+        (sentences, diagnostics)
+    }
 
-                    let expected_syllable = if let Some(s) = key_iter.next() {
-                        *s
+    /// Aligns `pekzep_hanzi` with the syllables `k` read from `pekzep_latin`.
+    /// On failure, returns the byte range in `pekzep_hanzi` at fault and the message.
+    fn align_sentence_of_row(
+        k: &[read::phrase::ExtSyllable],
+        pekzep_hanzi: &str,
+        pekzep_latin: &str,
+        char_pronunciation: &[(Linzklar, pekzep_syllable::PekZepSyllable)],
+        contraction_pronunciation: &[(LinzklarString, pekzep_syllable::PekZepSyllable)],
+    ) -> Result<PekzepSentence, (std::ops::Range<usize>, String)> {
+        use log::info;
+        let key = read::phrase::syllables_to_str_underscore(k);
+        let tokens = hanzi::tokenize(pekzep_hanzi).map_err(|e| {
+            (
+                e.span,
+                format!(
+                    "While trying to match {key} with {}: {}",
+                    pekzep_hanzi, e.message
+                ),
+            )
+        })?;
+        // `k` is read from `pekzep_latin`, one syllable per word
+        let mut key_iter = k
+            .iter()
+            .copied()
+            .zip(read::phrase::latin_words(pekzep_latin));
+        let mut elements = vec![];
+        for token in tokens {
+            let hanzi = match token.kind {
+                HanziToken::Space | HanziToken::Punctuation(_) => {
+                    info!("Skipped: {}", &pekzep_hanzi[token.span.clone()]);
+                    elements.push(Element::Punctuation(pekzep_hanzi[token.span].to_owned()));
+                    continue;
+                }
+                HanziToken::Xizi => SyllableHanzi::Xizi,
+                HanziToken::Linzklar(linzklar) => SyllableHanzi::Linzklar(linzklar),
+                // Handle exceptional contractions such as «足手» xiop1
+                HanziToken::Contraction(contraction) => SyllableHanzi::Contraction(contraction),
+                HanziToken::Blank
+                | HanziToken::BraceGroup(_)
+                | HanziToken::SplitMarker
+                | HanziToken::Placeholder(_) => {
+                    return Err((
+                        token.span.clone(),
+                        format!(
+                            "While trying to match {} with {}, found `{}`, which can only appear in the glosses of words",
+                            key, pekzep_hanzi, &pekzep_hanzi[token.span]
+                        ),
+                    ));
+                }
+            };
+            let Some((expected_syllable, (latin_span, latin))) = key_iter.next() else {
+                return Err((
+                    token.span,
+                    format!(
+                        "While trying to match {key} with {pekzep_hanzi}, end of key encountered"
+                    ),
+                ));
+            };
+            match &hanzi {
+                SyllableHanzi::Xizi => {
+                    if expected_syllable == read::phrase::ExtSyllable::Xizi {
+                        info!("matched `xizi`.");
                     } else {
-                        return Err(anyhow!(
-                            "While trying to match {:?} with {}, end of key encountered",
-                            k,
-                            v.pekzep_hanzi
+                        return Err((
+                            token.span,
+                            format!(
+                                "While trying to match {key} with {pekzep_hanzi}, mismatch found: pekzep_hanzi gave `xizi` but the key was something else"
+                            ),
                         ));
-                    };
-
-                    if let Some(a) = contraction_pronunciation.iter().find(|(h, syllable)| {
-                        *h.to_string() == contraction
+                    }
+                }
+                SyllableHanzi::Linzklar(linzklar) => {
+                    if let Some(a) = char_pronunciation.iter().find(|(h, syllable)| {
+                        h == linzklar
                             && read::phrase::ExtSyllable::Syllable(*syllable) == expected_syllable
                     }) {
                         info!("matched {} with {}", a.0, a.1);
                     } else {
-                        return Err(anyhow!(
-                            "While trying to match {:?} with {}, cannot find the contracted pronunciation `{}` for the character sequence `{}`",
-                            k,
-                            v.pekzep_hanzi,
-                            expected_syllable,
-                            contraction
-                        ));
-                    }
-                } else if c == 'x' {
-                    if Some('i') == iter.next()
-                        && Some('z') == iter.next()
-                        && Some('i') == iter.next()
-                    {
-                        if key_iter.next() == Some(&read::phrase::ExtSyllable::Xizi) {
-                            info!("matched `xizi`.");
-                        } else {
-                            return Err(anyhow!(
-                                "While trying to match {:?} with {}, mismatch found: pekzep_hanzi gave `xizi` but the key was something else",
-                                k,
-                                v.pekzep_hanzi
-                            ));
-                        }
-                    } else {
-                        return Err(anyhow!(
-                            "While trying to match {:?} with {}, expected `xizi` because `x` was encountered, but did not find it.",
-                            k,
-                            v.pekzep_hanzi
+                        return Err((
+                            token.span,
+                            format!(
+                                "While trying to match {key} with {pekzep_hanzi}, cannot find the pronunciation `{expected_syllable}` for character `{linzklar}`"
+                            ),
                         ));
                     }
-                } else {
-                    let expected_syllable = if let Some(s) = key_iter.next() {
-                        *s
-                    } else {
-                        return Err(anyhow!(
-                            "While trying to match {:?} with {}, end of key encountered",
-                            k,
-                            v.pekzep_hanzi
-                        ));
-                    };
-                    if let Some(a) = char_pronunciation.iter().find(|(h, syllable)| {
-                        *h.to_string() == c.to_string()
+                }
+                SyllableHanzi::Contraction(contraction) => {
+                    if let Some(a) = contraction_pronunciation.iter().find(|(h, syllable)| {
+                        h == contraction
                             && read::phrase::ExtSyllable::Syllable(*syllable) == expected_syllable
                     }) {
                         info!("matched {} with {}", a.0, a.1);
                     } else {
-                        return Err(anyhow!(
-                            "While trying to match {:?} with {}, cannot find the pronunciation `{}` for character `{}`",
-                            k,
-                            v.pekzep_hanzi,
-                            expected_syllable,
-                            c
+                        return Err((
+                            token.span,
+                            format!(
+                                "While trying to match {key} with {pekzep_hanzi}, cannot find the contracted pronunciation `{expected_syllable}` for the character sequence `{contraction}`"
+                            ),
                         ));
                     }
                 }
             }
+            elements.push(Element::Syllable(AlignedSyllable {
+                hanzi,
+                syllable: expected_syllable,
+                latin: latin.to_owned(),
+                hanzi_span: token.span,
+                latin_span,
+            }));
+        }
 
-            if let Some(a) = key_iter.next() {
-                return Err(anyhow!(
-                    "Encountered {} but `{}` ended earlier.\n\nThis occurred while trying to match {:?} with {}, ",
-                    a,
-                    v.pekzep_hanzi,
-                    k,
-                    v.pekzep_hanzi,
-                ));
+        if let Some((a, _)) = key_iter.next() {
+            return Err((
+                0..pekzep_hanzi.len(),
+                format!(
+                    "Encountered {a} but `{pekzep_hanzi}` ended earlier. This occurred while trying to match {key} with {pekzep_hanzi}",
+                ),
+            ));
+        }
+        Ok(PekzepSentence(elements))
+    }
+
+    /// Looks up the phrases of each lesson in `positions`, which gives the index in `rows3` of each phrase key,
+    /// reporting the keys not found and the empty ranges.
+    fn resolve_lessons(
+        path: &Path,
+        items: Vec<read::lesson::Item>,
+        positions: &HashMap<String, usize>,
+    ) -> Result<Vec<Lesson>, Vec<Diagnostic>> {
+        let mut lessons = vec![];
+        let mut errors = vec![];
+        for item in items {
+            let mut phrases = vec![];
+            for range in &item.phrases {
+                let cell = || {
+                    let text = item
+                        .phrases
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    Cell::whole("phrases", &text)
+                };
+                match (positions.get(&range.first), positions.get(&range.last)) {
+                    (Some(&first), Some(&last)) if first <= last => phrases.extend(first..=last),
+                    (Some(_), Some(_)) => errors.push(
+                        Diagnostic::error(
+                            "invalid-lesson-range",
+                            path,
+                            item.line,
+                            format!(
+                                "`{}` comes after `{}` in the spreadsheet, so the range `{range}` is empty",
+                                range.first, range.last
+                            ),
+                        )
+                        .with_cell(cell()),
+                    ),
+                    _ => {
+                        let mut keys = vec![&range.first];
+                        if range.last != range.first {
+                            keys.push(&range.last);
+                        }
+                        for key in keys {
+                            if !positions.contains_key(key) {
+                                errors.push(
+                                    Diagnostic::error(
+                                        "unknown-lesson-phrase",
+                                        path,
+                                        item.line,
+                                        format!("the phrase `{key}` in the lesson `{}` is not found", item.title_en),
+                                    )
+                                    .with_cell(cell()),
+                                );
+                            }
+                        }
+                    }
+                }
             }
+            lessons.push(Lesson {
+                title_en: item.title_en,
+                title_zh: item.title_zh,
+                title_ja: item.title_ja,
+                phrases,
+            });
         }
-        Ok(())
+        if errors.is_empty() {
+            Ok(lessons)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Reports the phrases in a lesson that have no decomposition, since their worksheets have no fill-in exercise for them.
+    fn check_lesson_decomposition(
+        phrase_path: &Path,
+        lessons: &[Lesson],
+        rows3: &[Rows3Item],
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        for lesson in lessons {
+            for &i in &lesson.phrases {
+                let item = &rows3[i];
+                if item.decomposition.is_empty() {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "undecomposed-lesson-phrase",
+                            phrase_path,
+                            item.row.line,
+                            format!(
+                                "`{}` in the lesson `{}` has no decomposition, so the worksheet has no fill-in exercise for it",
+                                read::phrase::syllables_to_str_underscore(&item.syllables),
+                                lesson.title_en
+                            ),
+                        )
+                        .with_cell(Cell::whole("decomposed", "")),
+                    );
+                }
+            }
+        }
+        diagnostics
     }
 
     fn char_count(
@@ -140,345 +397,531 @@ impl DataBundle {
     ) -> anyhow::Result<HashMap<Linzklar, usize>> {
         use log::info;
         let mut ans = HashMap::new();
-        for (_, v) in spoonfed_rows {
-            let mut iter = v.pekzep_hanzi.chars();
-            while let Some(c) = iter.next() {
-                if c.is_whitespace() || c.is_ascii_punctuation() || "！？「」。".contains(c) {
-                    info!("Skipped: {c}");
-                } else if c == '«' {
+        for (k, v) in spoonfed_rows {
+            let tokens = hanzi::tokenize(&v.pekzep_hanzi).with_context(|| {
+                format!(
+                    "while counting the characters in the phrase {}",
+                    read::phrase::syllables_to_str_underscore(k)
+                )
+            })?;
+            for token in tokens {
+                match token.kind {
+                    HanziToken::Linzklar(linzklar) => *ans.entry(linzklar).or_insert(0_usize) += 1,
                     // Handle exceptional contractions such as «足手» xiop1
-                    let mut c = iter.next().expect("Unmatched guillemet");
-                    loop {
-                        if c == '»' {
-                            break;
+                    HanziToken::Contraction(contraction) => {
+                        for linzklar in contraction.0 {
+                            *ans.entry(linzklar).or_insert(0_usize) += 1;
                         }
-                        let key = Linzklar::from_char(c)?;
-                        let count = ans.entry(key).or_insert(0_usize);
-                        *count += 1;
-                        c = iter.next().expect("Unmatched guillemet");
-                    }
-                } else if c == 'x' {
-                    if Some('i') == iter.next()
-                        && Some('z') == iter.next()
-                        && Some('i') == iter.next()
-                    {
-                    } else {
-                        return Err(anyhow!("xizi expected, but found something else."));
                     }
-                } else {
-                    let key = Linzklar::from_char(c)?;
-                    let count = ans.entry(key).or_insert(0_usize);
-                    *count += 1;
+                    _ => info!("Skipped: {}", &v.pekzep_hanzi[token.span]),
                 }
             }
         }
         Ok(ans)
     }
 
-    fn match_xizi(hanzi_iter: &mut std::str::Chars, v: &read::vocab::Item) -> anyhow::Result<()> {
-        use log::info;
-        match hanzi_iter.next() {
-            Some('x') => {
-                if hanzi_iter.next() == Some('i')
-                    && hanzi_iter.next() == Some('z')
-                    && hanzi_iter.next() == Some('i')
-                {
-                    info!("matched `xizi` with `xizi`");
-                }
-            }
-            Some(' ') => {
-                if hanzi_iter.next() == Some('x')
-                    && hanzi_iter.next() == Some('i')
-                    && hanzi_iter.next() == Some('z')
-                    && hanzi_iter.next() == Some('i')
-                {
-                    info!("matched `xizi` with `xizi`");
-                }
-            }
-            _ => {
-                return Err(anyhow!(
-                    "While trying to match {:?} with {}, cannot find matching xizi.",
-                    v.pekzep_hanzi,
-                    v.pekzep_latin
-                ));
-            }
-        }
-        Ok(())
-    }
-
     fn check_vocab_pronunciation(
+        path: &Path,
         vocab: &HashMap<InternalKey, read::vocab::Item>,
         char_pronunciation: &[(Linzklar, pekzep_syllable::PekZepSyllable)],
         contraction_pronunciation: &[(LinzklarString, pekzep_syllable::PekZepSyllable)],
-    ) -> anyhow::Result<()> {
-        use log::info;
+    ) -> Vec<Diagnostic> {
         eprintln!(
             "Checking if the pronunciations of the glosses are correct. Run with RUST_LOG environment variable set to `info` to see the details."
         );
-        // let mut pronunciation_errors_in_vocab = vec![];
-        for v in vocab.values() {
-            if v.pekzep_hanzi == "∅" && v.pekzep_latin.is_empty() {
-                info!("matched `∅` with an empty string");
-            }
-            let mut latin_iter = v.pekzep_latin.split(char::is_whitespace);
-            let mut hanzi_iter = v.pekzep_hanzi.chars();
-            'a: while let Some(s) = latin_iter.next() {
-                if s == "xizi" {
-                    Self::match_xizi(&mut hanzi_iter, v)?;
-                }
+        vocab
+            .iter()
+            .filter_map(|(k, v)| {
+                Self::check_vocab_pronunciation_of_item(
+                    k,
+                    v,
+                    char_pronunciation,
+                    contraction_pronunciation,
+                )
+                .err()
+                .map(|(range, message)| {
+                    Diagnostic::error("vocab-pronunciation", path, v.line, message)
+                        .with_cell(Cell::new("pekzep_hanzi", &v.pekzep_hanzi, range))
+                })
+            })
+            .collect()
+    }
 
-                if let Some(syllable) = PekZepSyllable::parse(s) {
-                    let mut c = hanzi_iter.next().expect("Unmatched syllable");
-                    loop {
-                        if !c.is_whitespace() {
-                            break;
-                        }
-                        c = hanzi_iter.next().expect("Unmatched syllable");
+    fn check_vocab_pronunciation_of_item(
+        k: &InternalKey,
+        v: &read::vocab::Item,
+        char_pronunciation: &[(Linzklar, pekzep_syllable::PekZepSyllable)],
+        contraction_pronunciation: &[(LinzklarString, pekzep_syllable::PekZepSyllable)],
+    ) -> Result<(), (std::ops::Range<usize>, String)> {
+        use log::info;
+        if v.pekzep_hanzi == "∅" && v.pekzep_latin.is_empty() {
+            info!("matched `∅` with an empty string");
+        }
+        let mismatch = |span: Option<std::ops::Range<usize>>, message: String| {
+            (
+                span.unwrap_or(v.pekzep_hanzi.len()..v.pekzep_hanzi.len()),
+                format!(
+                    "While trying to match {} with {} (the word {k}), {message}",
+                    v.pekzep_hanzi, v.pekzep_latin
+                ),
+            )
+        };
+        let tokens =
+            hanzi::tokenize(&v.pekzep_hanzi).map_err(|e| mismatch(Some(e.span), e.message))?;
+        let mut hanzi_iter = tokens
+            .into_iter()
+            .filter(|token| token.kind != HanziToken::Space)
+            .peekable();
+        let mut latin_iter = v.pekzep_latin.split(char::is_whitespace);
+        while let Some(s) = latin_iter.next() {
+            if s == "xizi" {
+                match hanzi_iter.next() {
+                    Some(token) if token.kind == HanziToken::Xizi => {
+                        info!("matched `xizi` with `xizi`");
+                    }
+                    token => {
+                        return Err(mismatch(
+                            token.map(|token| token.span),
+                            "cannot find matching xizi.".to_owned(),
+                        ));
                     }
-                    if c == '«' {
-                        // Handle exceptional contractions such as «足手» xiop1
-                        let mut contraction = String::new();
+                }
+            } else if let Some(syllable) = PekZepSyllable::parse(s) {
+                let Some(token) = hanzi_iter.next() else {
+                    return Err(mismatch(
+                        None,
+                        format!("`{syllable}` has no corresponding character"),
+                    ));
+                };
+                match token.kind {
+                    // Handle exceptional contractions such as «足手» xiop1
+                    HanziToken::Contraction(contraction) => {
+                        if let Some(a) = contraction_pronunciation
+                            .iter()
+                            .find(|(h, sy)| *h == contraction && *sy == syllable)
                         {
-                            let mut c = hanzi_iter.next().expect("Unmatched guillemet");
-                            loop {
-                                if c == '»' {
-                                    break;
-                                }
-                                contraction.push(c);
-                                c = hanzi_iter.next().expect("Unmatched guillemet");
-                            }
+                            info!("matched {} with {}", a.0, a.1);
+                        } else {
+                            return Err(mismatch(
+                                Some(token.span),
+                                format!(
+                                    "cannot find the contracted pronunciation `{syllable}` for `«{contraction}»`"
+                                ),
+                            ));
                         }
-                        if let Some(a) = contraction_pronunciation
+                    }
+                    HanziToken::Linzklar(linzklar) => {
+                        if let Some(a) = char_pronunciation
                             .iter()
-                            .find(|(h, sy)| *h.to_string() == contraction && *sy == syllable)
+                            .find(|(h, sy)| *h == linzklar && *sy == syllable)
                         {
                             info!("matched {} with {}", a.0, a.1);
                         } else {
-                            return Err(anyhow!(
-                                "While trying to match {} with {}, cannot find the contracted pronunciation `{}` for `«{}»`",
-                                syllable,
-                                contraction,
-                                syllable,
-                                c
+                            return Err(mismatch(
+                                Some(token.span),
+                                format!(
+                                    "cannot find the pronunciation `{syllable}` for character `{linzklar}`"
+                                ),
                             ));
                         }
-                    } else if let Some(a) = char_pronunciation
-                        .iter()
-                        .find(|(h, sy)| *h.to_string() == c.to_string() && *sy == syllable)
-                    {
-                        info!("matched {} with {}", a.0, a.1);
-                    } else {
-                        return Err(anyhow!(
-                            "While trying to match {} with {}, cannot find the pronunciation `{}` for character `{}`",
-                            v.pekzep_hanzi,
-                            v.pekzep_latin,
-                            syllable,
-                            c
+                    }
+                    _ => {
+                        return Err(mismatch(
+                            Some(token.span.clone()),
+                            format!(
+                                "cannot find the pronunciation `{syllable}` for `{}`",
+                                &v.pekzep_hanzi[token.span]
+                            ),
                         ));
                     }
-                } else if s == "//" {
-                    if hanzi_iter.next() == Some(' ')
-                        && hanzi_iter.next() == Some('/')
-                        && hanzi_iter.next() == Some('/')
-                        && hanzi_iter.next() == Some(' ')
-                    {
+                }
+            } else if s == "//" {
+                match hanzi_iter.next() {
+                    Some(token) if token.kind == HanziToken::SplitMarker => {
                         info!("matched `//` with `//`");
                     }
-                } else if s == "S" {
-                    if hanzi_iter.next() == Some('S') && hanzi_iter.next() == Some(' ') {
+                    token => {
+                        return Err(mismatch(
+                            token.map(|token| token.span),
+                            "cannot find matching `//`".to_owned(),
+                        ));
+                    }
+                }
+            } else if s == "S" {
+                match hanzi_iter.next() {
+                    Some(token) if token.kind == HanziToken::Placeholder('S') => {
                         info!("matched `S` with `S`");
                     }
-                } else {
-                    match s.chars().next() {
-                        Some('{') => {
-                            // for the latin side, start ignoring everything else until the matching '}'
-                            let mut u = s;
-                            loop {
-                                if u.ends_with('}') {
-                                    break;
-                                }
-                                u = latin_iter.next().expect("Unmatched }");
-                            }
+                    token => {
+                        return Err(mismatch(
+                            token.map(|token| token.span),
+                            "cannot find matching `S`".to_owned(),
+                        ));
+                    }
+                }
+            } else if s.starts_with('{') {
+                // for the latin side, ignore everything else until the matching '}'
+                let mut u = s;
+                while !u.ends_with('}') {
+                    u = latin_iter.next().ok_or_else(|| {
+                        mismatch(
+                            None,
+                            "found `{` without the matching `}` in the latin".to_owned(),
+                        )
+                    })?;
+                }
 
-                            // for the hanzi side, skip
-                            loop {
-                                match hanzi_iter.next() {
-                                    Some(' ') => { /* continue */ },
-                                    Some('{') => break,
-                                    None => continue 'a,
-                                    Some(_) => panic!(
-                                        "Trying to match {} with {}: Unexpected char {s:?} found while dealing with braces",
-                                        v.pekzep_hanzi, v.pekzep_latin
-                                    ),
-                                }
-                            }
-                            loop {
-                                match hanzi_iter.next() {
-                                    Some('}') => break,
-                                    None => panic!("Unexpected end of the input"),
-                                    Some(_) => { /* continue */ },
-                                }
-                            }
-                        }
-                        Some(_) => { /* continue */ },
-                        None => break,
+                // for the hanzi side, the braces may be omitted
+                match hanzi_iter.peek() {
+                    None => {}
+                    Some(token) if matches!(token.kind, HanziToken::BraceGroup(_)) => {
+                        hanzi_iter.next();
+                    }
+                    Some(token) => {
+                        return Err(mismatch(
+                            Some(token.span.clone()),
+                            format!(
+                                "unexpected `{}` found while dealing with braces",
+                                &v.pekzep_hanzi[token.span.clone()]
+                            ),
+                        ));
                     }
                 }
+            } else if s.is_empty() {
+                break;
             }
         }
         Ok(())
     }
-    fn check_nonrecommended_character(s: &str, variants: &HashMap<Linzklar, Linzklar>) {
-        use log::warn;
+
+    fn check_nonrecommended_character(
+        path: &Path,
+        line: usize,
+        s: &str,
+        variants: &HashMap<Linzklar, Linzklar>,
+    ) -> Vec<Diagnostic> {
+        let mut ans = vec![];
         for (key, value) in variants {
-            if s.contains(&key.to_string()) {
-                warn!(
-                    "{s} contains {key}, which should be replaced with {value}"
+            for (i, found) in s.match_indices(&key.to_string()) {
+                ans.push(
+                    Diagnostic::warning(
+                        "nonrecommended-character",
+                        path,
+                        line,
+                        format!("{s} contains {key}, which should be replaced with {value}"),
+                    )
+                    .with_cell(Cell::new("pekzep_hanzi", s, i..i + found.len()))
+                    .with_suggestion(format!("replace `{key}` with `{value}`")),
                 );
             }
         }
+        ans
     }
 
-    fn check_kan1(pekzep_hanzi: &str, english: &str) {
-        use log::warn;
-        if pekzep_hanzi.contains('躍') {
-            if english.contains("jump") || english.contains("dance") {
-                return;
+    /// The images that rendering `tokens` shows, each with the byte range of the token it comes from
+    fn char_img_names(tokens: &[hanzi::Token], ans: &mut Vec<(String, std::ops::Range<usize>)>) {
+        for token in tokens {
+            let span = token.span.clone();
+            match &token.kind {
+                HanziToken::Linzklar(linzklar) => ans.push((linzklar.as_char().to_string(), span)),
+                HanziToken::Contraction(contraction) => ans.extend(
+                    contraction
+                        .0
+                        .iter()
+                        .map(|linzklar| (linzklar.as_char().to_string(), span.clone())),
+                ),
+                HanziToken::Xizi => {
+                    ans.push(("xi".to_owned(), span.clone()));
+                    ans.push(("zi".to_owned(), span));
+                }
+                HanziToken::Blank => ans.push(("blank".to_owned(), span)),
+                HanziToken::Punctuation(c) if !c.is_ascii() => ans.push((c.to_string(), span)),
+                HanziToken::BraceGroup(inner) => Self::char_img_names(inner, ans),
+                _ => {}
             }
-            warn!(
-                "{pekzep_hanzi} contains 躍, but the English translation did not contain the word 'jump' or 'dance'. Please check if the sentence `{english}` should contains the notion of 'jump'."
-            );
         }
     }
-    fn check_co1(pekzep_hanzi: &str, english: &str) {
-        use log::warn;
-        if pekzep_hanzi.contains('壁') {
-            if english.contains("wall") {
-                return;
+
+    /// Reports the images that the pages show but that are found neither in `source.char_img` nor in `source.char_img_fallback`.
+    /// Each image is reported once, at the first row using it: a phrase, else a word, else a line of "字音.tsv".
+    fn check_char_img(
+        source: &SourcePaths,
+        rows3: &[Rows3Item],
+        vocab_ordered: &LinkedHashMap<InternalKey, read::vocab::Item>,
+        char_pronunciation: &[(Linzklar, PekZepSyllable)],
+    ) -> Vec<Diagnostic> {
+        let phrases = rows3.iter().map(|item| {
+            (
+                &source.phrase,
+                item.row.line,
+                "pekzep_hanzi",
+                item.row.pekzep_hanzi.clone(),
+            )
+        });
+        let words = vocab_ordered.values().map(|item| {
+            (
+                &source.vocab,
+                item.line,
+                "pekzep_hanzi",
+                item.pekzep_hanzi.clone(),
+            )
+        });
+        // the first line of "字音.tsv" is the header
+        let chars = char_pronunciation
+            .iter()
+            .enumerate()
+            .map(|(i, (linzklar, _))| {
+                (
+                    &source.char_pronunciation,
+                    i + 2,
+                    "character",
+                    linzklar.to_string(),
+                )
+            });
+
+        // the first use of each image, and the number of the rows using it
+        let mut uses = LinkedHashMap::new();
+        for (path, line, field, text) in phrases.chain(words).chain(chars) {
+            let Ok(tokens) = hanzi::tokenize(&text) else {
+                // already reported
+                continue;
+            };
+            let mut names = vec![];
+            Self::char_img_names(&tokens, &mut names);
+            let mut seen = std::collections::HashSet::new();
+            for (name, span) in names {
+                if seen.insert(name.clone()) {
+                    let (_, count) = uses
+                        .entry(name)
+                        .or_insert_with(|| ((path, line, Cell::new(field, &text, span)), 0));
+                    *count += 1;
+                }
             }
-            warn!(
-                "{pekzep_hanzi} contains 壁, but the English translation did not contain the word 'wall'. Please check if the sentence `{english}` should contains the notion of 'jump'."
-            );
-        }
-    }
-    fn check_a(s: &str) {
-        use log::warn;
-        use regex::Regex;
-        use std::sync::LazyLock;
-        static RE_NO_PUNCTUATION_AFTER_噫: LazyLock<Regex> = LazyLock::new(|| Regex::new("噫[^)。！？」]").unwrap());
-
-        // 【之】の後に句読点あるなら警告
-        if s.contains("之。") || s.contains("之！") || s.contains("之？") || s.contains("之」")
-        {
-            warn!(
-                "punctuation after `之` is detected in `{s}`. Maybe replace it with `噫`?"
-            );
         }
 
-        // 【噫】の後に句読点も)もないなら警告
-        if RE_NO_PUNCTUATION_AFTER_噫.is_match(s) {
-            warn!(
-                "no punctuation found after `噫` in `{s}`. Maybe replace it with `之`."
-            );
-        }
+        uses.into_iter()
+            .filter(|(name, _)| {
+                let file = format!("{name}.png");
+                !source.char_img.join(&file).exists()
+                    && !source.char_img_fallback.join(&file).exists()
+            })
+            .map(|(name, ((path, line, cell), count))| {
+                Diagnostic::warning(
+                    "missing-char-img",
+                    path,
+                    line,
+                    format!("char_img not found: {name}.png, which is used in {count} row(s)"),
+                )
+                .with_cell(cell)
+            })
+            .collect()
     }
 
-    fn check_space_before_punctuation(s: &str) {
-        use log::warn;
-        if s.contains(" .") {
-            warn!(
-                "a space before a period is detected in `{s}`. Remove the space."
-            );
-        }
-
-        if s.contains(" ,") {
-            warn!(
-                "a space before a comma is detected in `{s}`. Remove the space."
-            );
+    /// Merges the errors from several files into one, so that all of them can be reported at once.
+    /// An error that is not a [`Diagnostics`] (such as an I/O error) is returned as is.
+    fn merge_errors(errors: impl IntoIterator<Item = anyhow::Error>) -> anyhow::Error {
+        let mut diagnostics = vec![];
+        for error in errors {
+            match error.downcast::<Diagnostics>() {
+                Ok(Diagnostics(found)) => diagnostics.extend(found),
+                Err(error) => return error,
+            }
         }
+        Diagnostics(diagnostics).into()
     }
 
+    /// Reads all the input files listed in `source` and checks their consistency.
+    /// The warnings found are stored in `diagnostics`, after being filtered by `lints`.
     /// # Errors
     /// Returns `Err` if the validation fails.
-    pub fn new() -> anyhow::Result<Self> {
-        use log::{info, warn};
-        use match_pinyin_with_hanzi::match_pinyin_with_hanzi;
-        let (char_pronunciation, variants) = read::char_pronunciation::parse()?;
-        let contraction_pronunciation = read::contraction::parse()?;
-
-        let spoonfed_rows = read::phrase::parse()?;
-        Self::check_sentence_pronunciation(
+    /// If the input is merely inconsistent, the error is a [`Diagnostics`] listing all the errors and the warnings found.
+    pub fn new(source: &SourcePaths, lints: &LintLevels) -> anyhow::Result<Self> {
+        use anyhow::Context as _;
+        let (char_pronunciation, variants) =
+            read::char_pronunciation::parse(&source.char_pronunciation)?;
+        let contraction_pronunciation = read::contraction::parse(&source.contraction)?;
+        let registry = Registry::new(read::lint_keyword::parse(&source.lint_keywords)?);
+        lints.validate(&registry)?;
+        let mut suppressions =
+            Suppressions::new(read::lint_suppression::parse(&source.lint_suppressions)?);
+
+        let (spoonfed_rows, vocab) = match (
+            read::phrase::parse(&source.phrase)
+                .with_context(|| format!("Cannot read {}", source.phrase.display())),
+            read::vocab::parse(&source.vocab)
+                .with_context(|| format!("Cannot read {}", source.vocab.display())),
+        ) {
+            (Ok(spoonfed_rows), Ok(vocab)) => (spoonfed_rows, vocab),
+            (spoonfed_rows, vocab) => {
+                return Err(Self::merge_errors(
+                    [spoonfed_rows.err(), vocab.err()].into_iter().flatten(),
+                ));
+            }
+        };
+
+        let (mut sentences, mut diagnostics) = Self::align_sentences(
+            &source.phrase,
             &spoonfed_rows,
             &char_pronunciation,
             &contraction_pronunciation,
-        )?;
-
-        for (_, item) in &spoonfed_rows {
-            Self::check_nonrecommended_character(&item.pekzep_hanzi, &variants);
-            Self::check_a(&item.pekzep_hanzi);
-            Self::check_kan1(&item.pekzep_hanzi, &item.english);
-            Self::check_co1(&item.pekzep_hanzi, &item.english);
-
-            Self::check_space_before_punctuation(&item.pekzep_latin);
-            Self::check_space_before_punctuation(&item.english);
-            Self::check_space_before_punctuation(&item.chinese_pinyin);
+        );
 
-            info!("parsing pinyin {:?}:", &item.chinese_pinyin);
-            if let Err(err) = match_pinyin_with_hanzi(&item.chinese_pinyin, &item.chinese_hanzi) {
-                warn!("{err}");
+        for (syllables, item) in &spoonfed_rows {
+            let mut found = Self::check_nonrecommended_character(
+                &source.phrase,
+                item.line,
+                &item.pekzep_hanzi,
+                &variants,
+            );
+            found.extend(registry.check(&source.phrase, item));
+            if item.pekzep_hanzi.is_empty() {
+                found.push(
+                    Diagnostic::warning(
+                        "empty-transcription",
+                        &source.phrase,
+                        item.line,
+                        format!("The transcription for `{}` is empty.", item.pekzep_latin),
+                    )
+                    .with_cell(Cell::whole("pekzep_hanzi", "")),
+                );
             }
+            diagnostics.extend(
+                suppressions.filter(&read::phrase::syllables_to_str_underscore(syllables), found),
+            );
         }
+        diagnostics.extend(suppressions.stale(&source.lint_suppressions));
 
-        let vocab = read::vocab::parse()?;
-        Self::check_vocab_pronunciation(&vocab, &char_pronunciation, &contraction_pronunciation)?;
+        diagnostics.extend(Self::check_vocab_pronunciation(
+            &source.vocab,
+            &vocab,
+            &char_pronunciation,
+            &contraction_pronunciation,
+        ));
 
         for item in vocab.values() {
-            Self::check_nonrecommended_character(&item.pekzep_hanzi, &variants);
+            diagnostics.extend(Self::check_nonrecommended_character(
+                &source.vocab,
+                item.line,
+                &item.pekzep_hanzi,
+                &variants,
+            ));
         }
 
         let mut vocab_ordered = LinkedHashMap::new();
         let mut vocab_count = HashMap::new();
+        let mut rows3 = vec![];
 
-        let rows3 = spoonfed_rows
-            .iter()
-            .map(|(syllables, row)| match verify_decomposed(&vocab, row) {
-                Ok(decomposition) => {
-                    for DecompositionItem {
-                        key,
-                        voc,
-                        splittable_compound_info: _,
-                    } in decomposition.iter().flatten()
-                    {
-                        if !vocab_ordered.contains_key(key) {
-                            vocab_ordered.insert((*key).clone(), voc.clone());
-                        }
-
-                        let count = vocab_count.entry((*key).clone()).or_insert(0_usize);
-                        *count += 1;
-                    }
-                    Ok(Rows3Item {
-                        syllables: syllables.clone(),
-                        decomposition,
-                        row: row.clone(),
-                    })
+        for (syllables, row) in &spoonfed_rows {
+            let decomposition = match verify_decomposed(&source.phrase, &vocab, row) {
+                Ok(decomposition) => decomposition,
+                Err(errors) => {
+                    diagnostics.extend(errors);
+                    continue;
                 }
-                Err(e) => Err(e),
-            })
-            .collect::<anyhow::Result<_>>()?;
+            };
+            for DecompositionItem {
+                key,
+                voc,
+                splittable_compound_info: _,
+            } in decomposition.iter().flatten()
+            {
+                if !vocab_ordered.contains_key(key) {
+                    vocab_ordered.insert((*key).clone(), voc.clone());
+                }
+
+                let count = vocab_count.entry((*key).clone()).or_insert(0_usize);
+                *count += 1;
+            }
+            let Some(sentence) = sentences.remove(syllables) else {
+                // the pronunciation did not match, which has already been reported
+                continue;
+            };
+            rows3.push(Rows3Item {
+                syllables: syllables.clone(),
+                sentence,
+                decomposition,
+                row: row.clone(),
+            });
+        }
 
-        for key in vocab.keys() {
-            if !vocab_ordered.contains_key(key) {
-                warn!("Item with internal key `{key}` is never used");
+        let positions = rows3
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                (
+                    read::phrase::syllables_to_str_underscore(&item.syllables),
+                    i,
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let lessons = match Self::resolve_lessons(
+            &source.lessons,
+            read::lesson::parse(&source.lessons)
+                .with_context(|| format!("Cannot read {}", source.lessons.display()))?,
+            &positions,
+        ) {
+            Ok(lessons) => lessons,
+            Err(errors) => {
+                diagnostics.extend(errors);
+                vec![]
             }
+        };
+
+        diagnostics.extend(Self::check_lesson_decomposition(
+            &source.phrase,
+            &lessons,
+            &rows3,
+        ));
+        diagnostics.extend(Self::check_char_img(
+            source,
+            &rows3,
+            &vocab_ordered,
+            &char_pronunciation,
+        ));
+
+        let mut diagnostics = lints.apply(diagnostics);
+        // the order in which the checks are run is kept within a line
+        diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(Diagnostics(diagnostics).into());
+        }
+
+        let unused = vocab
+            .iter()
+            .filter(|(key, _)| !vocab_ordered.contains_key(*key))
+            .map(|(key, item)| {
+                Diagnostic::warning(
+                    "unused-word",
+                    &source.vocab,
+                    item.line,
+                    format!("Item with internal key `{key}` is never used"),
+                )
+            })
+            .collect();
+        diagnostics.extend(lints.apply(unused));
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(Diagnostics(diagnostics).into());
         }
+        diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
 
         let char_count = Self::char_count(&spoonfed_rows)?;
+        let index = Index::new(
+            &rows3,
+            &vocab_ordered,
+            &char_pronunciation,
+            &contraction_pronunciation,
+            &variants,
+        )?;
 
         Ok(Self {
             rows3,
             vocab_ordered,
             vocab_count,
             char_count,
+            index,
+            graded: OnceLock::new(),
+            lessons,
+            diagnostics,
         })
     }
 }
@@ -493,60 +936,225 @@ pub struct DecompositionItem {
 /// Checks if:
 /// * all the morphemes listed in `row.decomposed` are in the vocab list
 /// * the `row.decomposed` really is a decomposition of `row.pekzep_hanzi`.
+///
+/// Every problem found in the row is reported, not just the first one.
 fn verify_decomposed(
+    path: &Path,
     vocab: &HashMap<InternalKey, read::vocab::Item>,
     row: &read::phrase::Item,
-) -> anyhow::Result<Vec<Vec<DecompositionItem>>> {
+) -> Result<Vec<Vec<DecompositionItem>>, Vec<Diagnostic>> {
     if row.decomposed.is_empty() {
-        Ok(vec![])
-    } else {
-        let rejoined = row
-            .decomposed
-            .iter()
-            .map(read::phrase::SentenceGloss::to_plaintext)
-            .collect::<String>();
-        let expectation = row
-            .pekzep_hanzi
-            .to_string()
-            .replace(['！', '？', '。', '「', '」'], "");
-        if rejoined != expectation {
-            return Err(anyhow!(
-                "mismatch: the original row gives {} but the decomposition is {}",
-                expectation,
-                rejoined
-            ));
-        }
-        let debug_string = row
-            .decomposed
-            .iter()
-            .map(read::phrase::SentenceGloss::to_debugtext)
-            .collect::<Vec<_>>()
-            .join("..");
+        return Ok(vec![]);
+    }
+    // the content of the cell, as written in the tsv
+    let debug_string = row
+        .decomposed
+        .iter()
+        .map(read::phrase::SentenceGloss::to_debugtext)
+        .collect::<Vec<_>>()
+        .join("..");
 
-        let mut ans = vec![];
-        for s in &row.decomposed {
-            ans.push(
-                s.0.iter()
-                    .map(|key_gloss| {
-                        let key = key_gloss.to_internal_key();
-                        let splittable_compound_info = key_gloss.to_splittable_compound_info();
-                        let res = vocab.get(&key).ok_or_else(|| anyhow! {
-                            format!(
-                                "Cannot find key {} in the vocab list, found while analyzing {}",
-                                &key,
-                                debug_string
-                            )
-                        });
-
-                        Ok(DecompositionItem {
-                            key,
-                            voc: res?.clone(),
-                            splittable_compound_info,
-                        })
-                    })
-                    .collect::<anyhow::Result<_>>()?,
-            );
+    let rejoined = row
+        .decomposed
+        .iter()
+        .map(read::phrase::SentenceGloss::to_plaintext)
+        .collect::<String>();
+    let expectation = row.pekzep_hanzi.replace(['！', '？', '。', '「', '」'], "");
+    if rejoined != expectation {
+        return Err(vec![
+            Diagnostic::error(
+                "decomposition-mismatch",
+                path,
+                row.line,
+                format!(
+                    "mismatch: the original row gives {expectation} but the decomposition is {rejoined}"
+                ),
+            )
+            .with_cell(Cell::whole("decomposed", &debug_string)),
+        ]);
+    }
+
+    let mut ans = vec![];
+    let mut errors = vec![];
+    let mut offset = 0;
+    for s in &row.decomposed {
+        let mut sentence = vec![];
+        for key_gloss in &s.0 {
+            let key = key_gloss.to_internal_key();
+            let splittable_compound_info = key_gloss.to_splittable_compound_info();
+            let len = key_gloss.to_string().len();
+            match vocab.get(&key) {
+                Some(voc) => sentence.push(DecompositionItem {
+                    voc: voc.clone(),
+                    key,
+                    splittable_compound_info,
+                }),
+                None => errors.push(
+                    Diagnostic::error(
+                        "unknown-word",
+                        path,
+                        row.line,
+                        format!(
+                            "Cannot find key {key} in the vocab list, found while analyzing {debug_string}"
+                        ),
+                    )
+                    .with_cell(Cell::new("decomposed", &debug_string, offset..offset + len)),
+                ),
+            }
+            offset += len + ".".len();
         }
+        // `..` separates the sentences
+        offset += ".".len();
+        ans.push(sentence);
+    }
+    if errors.is_empty() {
         Ok(ans)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DataBundle;
+    use crate::read::char_pronunciation::{Linzklar, LinzklarString};
+    use crate::read::lesson::{Item, PhraseRange};
+    use crate::read::phrase::encode_to_pekzep_syllables;
+    use crate::sentence::{Element, PekzepSentence, SyllableHanzi};
+    use pekzep_syllable::PekZepSyllable;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn align(
+        pekzep_hanzi: &str,
+        pekzep_latin: &str,
+    ) -> Result<PekzepSentence, (std::ops::Range<usize>, String)> {
+        let linzklar = |c| Linzklar::from_char(c).unwrap();
+        let syllable = |s| PekZepSyllable::parse(s).unwrap();
+        let char_pronunciation = [
+            (linzklar('汝'), syllable("mua2")),
+            (linzklar('言'), syllable("zep1")),
+            (linzklar('何'), syllable("nan2")),
+        ];
+        let contraction_pronunciation = [(
+            LinzklarString(vec![linzklar('足'), linzklar('手')]),
+            syllable("xiop1"),
+        )];
+        DataBundle::align_sentence_of_row(
+            &encode_to_pekzep_syllables(&pekzep_latin.to_lowercase()).unwrap(),
+            pekzep_hanzi,
+            pekzep_latin,
+            &char_pronunciation,
+            &contraction_pronunciation,
+        )
+    }
+
+    #[test]
+    fn syllables_are_aligned_with_the_latin() {
+        let sentence = align("汝言何。", "Mua2 zep1 nan2?").unwrap();
+        let syllables = sentence.syllables().collect::<Vec<_>>();
+        assert_eq!(
+            syllables
+                .iter()
+                .map(|s| (
+                    s.hanzi.to_string(),
+                    s.latin.as_str(),
+                    s.hanzi_span.clone(),
+                    s.latin_span.clone()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("汝".to_owned(), "Mua2", 0..3, 0..4),
+                ("言".to_owned(), "zep1", 3..6, 5..9),
+                ("何".to_owned(), "nan2", 6..9, 10..14),
+            ]
+        );
+        assert_eq!(
+            sentence.0.last(),
+            Some(&Element::Punctuation("。".to_owned()))
+        );
+    }
+
+    #[test]
+    fn contraction_and_xizi_are_one_syllable_each() {
+        let sentence = align("xizi «足手»", "xizi xiop1").unwrap();
+        assert_eq!(
+            sentence
+                .syllables()
+                .map(|s| s.hanzi.clone())
+                .collect::<Vec<_>>(),
+            [
+                SyllableHanzi::Xizi,
+                SyllableHanzi::Contraction(LinzklarString(vec![
+                    Linzklar::from_char('足').unwrap(),
+                    Linzklar::from_char('手').unwrap(),
+                ])),
+            ]
+        );
+    }
+
+    #[test]
+    fn mismatches_are_located_in_the_hanzi() {
+        // 言 is not read as nan2
+        assert_eq!(align("汝言", "mua2 nan2").unwrap_err().0, 3..6);
+        // the latin has a syllable left over
+        assert_eq!(align("汝言", "mua2 zep1 nan2").unwrap_err().0, 0..6);
+        // the hanzi has a character left over
+        assert_eq!(align("汝言何", "mua2 zep1").unwrap_err().0, 6..9);
+        // braces can only appear in the words
+        assert_eq!(align("汝{言}", "mua2 zep1").unwrap_err().0, 3..8);
+    }
+
+    fn lesson(ranges: &[&str]) -> Item {
+        Item {
+            title_en: "Greetings".to_owned(),
+            title_zh: String::new(),
+            title_ja: String::new(),
+            phrases: ranges.iter().map(|s| PhraseRange::new(s)).collect(),
+            line: 2,
+        }
+    }
+
+    /// The phrases of the lesson, or the rules and the cells of the errors
+    fn resolve(ranges: &[&str]) -> Result<Vec<usize>, Vec<(String, String)>> {
+        let positions = ["kait_kia1", "mua2_zep1_nan2", "pai2_hia1_mok1"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| (key.to_owned(), i))
+            .collect::<HashMap<_, _>>();
+        DataBundle::resolve_lessons(Path::new("lessons.tsv"), vec![lesson(ranges)], &positions)
+            .map(|lessons| lessons[0].phrases.clone())
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|e| (e.rule, e.cell.unwrap().text))
+                    .collect()
+            })
+    }
+
+    #[test]
+    fn lesson_ranges_are_expanded_in_the_order_of_the_spreadsheet() {
+        assert_eq!(resolve(&["kait_kia1..pai2_hia1_mok1"]), Ok(vec![0, 1, 2]));
+        assert_eq!(resolve(&["pai2_hia1_mok1", "kait_kia1"]), Ok(vec![2, 0]));
+    }
+
+    #[test]
+    fn unknown_lesson_phrases_and_empty_ranges_are_reported() {
+        assert_eq!(
+            resolve(&["pai2_hia1_mok1..kait_kia1"]),
+            Err(vec![(
+                "invalid-lesson-range".to_owned(),
+                "pai2_hia1_mok1..kait_kia1".to_owned()
+            )])
+        );
+        // each end of a range is looked up, and the whole cell is pointed at
+        let cell = "kait_kia2..mok1, kait_kia1".to_owned();
+        assert_eq!(
+            resolve(&["kait_kia2..mok1", "kait_kia1"]),
+            Err(vec![
+                ("unknown-lesson-phrase".to_owned(), cell.clone()),
+                ("unknown-lesson-phrase".to_owned(), cell)
+            ])
+        );
     }
 }
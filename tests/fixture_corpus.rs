@@ -0,0 +1,588 @@
+use pekzep_syllable::PekZepSyllable;
+use spoonfed_pekzep::config::{Config, GradedSettings, OutputPaths, SourcePaths};
+use spoonfed_pekzep::diagnostic::{Diagnostics, Severity};
+use spoonfed_pekzep::index::CharUsage;
+use spoonfed_pekzep::lint::LintLevels;
+use spoonfed_pekzep::manifest::{self, Build};
+use spoonfed_pekzep::read::char_pronunciation::Linzklar;
+use spoonfed_pekzep::verify::DataBundle;
+use std::path::Path;
+
+fn minimal_config(output_name: &str) -> Config {
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join(output_name);
+    for dir in ["phrase", "vocab", "char", "char_img"] {
+        std::fs::create_dir_all(output.join(dir)).unwrap();
+    }
+    Config {
+        source: SourcePaths::in_dir(Path::new("tests/fixtures/minimal")),
+        output: OutputPaths::in_dir(&output),
+        lints: LintLevels::default(),
+        graded: GradedSettings::default(),
+    }
+}
+
+#[test]
+fn minimal_corpus_is_verified() {
+    let config = minimal_config("minimal_corpus_is_verified");
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+
+    // the row marked with `@` is not yet reviewed and hence is skipped
+    assert_eq!(data_bundle.rows3.len(), 4);
+    assert_eq!(data_bundle.vocab_ordered.len(), 12);
+    assert_eq!(
+        data_bundle.char_count[&Linzklar::from_char('足').unwrap()],
+        1
+    );
+
+    // 汝言何？ / mua2 zep1 nan2?
+    assert_eq!(data_bundle.rows3[1].sentence.syllables().count(), 3);
+
+    // the fixture has no images, so each of them is reported once, at the first row using it
+    let missing = data_bundle
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule == "missing-char-img")
+        .map(|d| {
+            let cell = d.cell.as_ref().unwrap();
+            (d.file.clone(), d.line, cell.field, cell.column, cell.len)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(missing.len(), 18);
+    // 此猫之«足手»白。
+    assert!(missing.contains(&(config.source.phrase.clone(), 4, "pekzep_hanzi", 4, 4)));
+    // 噫 is not used in the phrases nor in the words
+    assert!(missing.contains(&(
+        config.source.char_pronunciation.clone(),
+        3,
+        "character",
+        1,
+        1
+    )));
+
+    // the phrases at the steps 2 to 4 of the graded order introduce 3, 3 and 5 new words
+    let too_many = data_bundle.check_graded(&config.source.phrase, &config.graded);
+    assert_eq!(
+        too_many
+            .iter()
+            .map(|d| (d.rule.as_str(), d.severity))
+            .collect::<Vec<_>>(),
+        [("too-many-new-words", Severity::Warning); 3]
+    );
+}
+
+#[test]
+fn data_bundle_is_indexed() {
+    let config = minimal_config("data_bundle_is_indexed");
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let index = &data_bundle.index;
+    let words = |keys: &[spoonfed_pekzep::read::vocab::InternalKey]| {
+        keys.iter()
+            .map(|key| key.to_path_safe_string())
+            .collect::<Vec<_>>()
+    };
+    let linzklar = |c| Linzklar::from_char(c).unwrap();
+
+    // 我 is used in 我心行。 only, since the unreviewed row is skipped
+    let pai2 = data_bundle
+        .vocab_ordered
+        .keys()
+        .find(|key| key.to_path_safe_string() == "我")
+        .unwrap();
+    assert_eq!(index.phrases_by_vocab(pai2), [2]);
+    assert_eq!(
+        index.phrases_by_linzklar_usage(linzklar('手'), CharUsage::Contraction),
+        [3]
+    );
+    assert_eq!(
+        words(index.vocab_by_part_of_speech("verb")),
+        ["言", "行", "白"]
+    );
+    assert_eq!(
+        index.chars_by_syllable(PekZepSyllable::parse("a").unwrap()),
+        [linzklar('之'), linzklar('噫')]
+    );
+}
+
+#[test]
+fn minimal_corpus_is_generated() {
+    let config = minimal_config("minimal_corpus_is_generated");
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+
+    spoonfed_pekzep::generate::phrases::r#gen(&data_bundle, &config, &build).unwrap();
+    spoonfed_pekzep::generate_index(&data_bundle, &config, &build).unwrap();
+
+    let phrase_page =
+        std::fs::read_to_string(config.output.phrase_dir().join("mua2_zep1_nan2.html")).unwrap();
+    assert!(phrase_page.contains("What are you saying?"));
+    assert!(config.output.file("index.html").exists());
+
+    // a contraction shows one syllable over two glyphs
+    let phrase_page = std::fs::read_to_string(
+        config
+            .output
+            .phrase_dir()
+            .join("ka1_ne2_a_xiop1_liok1.html"),
+    )
+    .unwrap();
+    assert!(phrase_page.contains(r#"<img src="../char_img/足.png" height="30"></a><a href="../char/手.html"><img src="../char_img/手.png" height="30"></a></span><rt>xiop1</rt></ruby>"#));
+}
+
+/// Copies the minimal corpus so that a test can modify it.
+fn copy_of_minimal_corpus(name: &str) -> SourcePaths {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    for entry in std::fs::read_dir("tests/fixtures/minimal").unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+    }
+    SourcePaths::in_dir(&dir)
+}
+
+#[test]
+fn all_errors_are_reported() {
+    let source = copy_of_minimal_corpus("all_errors_are_reported");
+    let phrases = std::fs::read_to_string(&source.phrase)
+        .unwrap()
+        .replace("汝言何？", "汝言我？") // line 2: wrong pronunciation
+        .replace("我.心.行", "我.心.走"); // line 3: decomposition mismatch
+    std::fs::write(&source.phrase, phrases).unwrap();
+
+    let err = DataBundle::new(&source, &LintLevels::default())
+        .err()
+        .unwrap();
+    let Diagnostics(diagnostics) = err.downcast_ref().unwrap();
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| (d.line, d.rule.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            (2, "sentence-pronunciation"),
+            (2, "decomposition-mismatch"),
+            (3, "decomposition-mismatch")
+        ]
+    );
+}
+
+#[test]
+fn suppressions_are_applied_and_stale_ones_reported() {
+    let source = copy_of_minimal_corpus("suppressions_are_applied_and_stale_ones_reported");
+    let phrases = std::fs::read_to_string(&source.phrase)
+        .unwrap()
+        .replace("I want to go.", "I want to go ."); // line 3
+    std::fs::write(&source.phrase, phrases).unwrap();
+    std::fs::write(
+        &source.lint_suppressions,
+        "phrase\trule\treason\npai2_hia1_mok1\tspace-before-punctuation\ttesting\nmua2_zep1_nan2\tkan1\n",
+    )
+    .unwrap();
+
+    let data_bundle = DataBundle::new(&source, &LintLevels::default()).unwrap();
+    let found = data_bundle
+        .diagnostics
+        .iter()
+        // the fixture has no images
+        .filter(|d| d.rule != "missing-char-img")
+        .map(|d| (d.file.clone(), d.line, d.rule.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        [(source.lint_suppressions.clone(), 3, "stale-suppression")]
+    );
+}
+
+#[test]
+fn malformed_cells_are_reported_instead_of_panicking() {
+    let source = copy_of_minimal_corpus("malformed_cells_are_reported_instead_of_panicking");
+    let vocab = std::fs::read_to_string(&source.vocab)
+        .unwrap()
+        .replace("言\tzep1\t言\t", "言\tzep1\t«言\t"); // line 3: unmatched guillemet
+    std::fs::write(&source.vocab, vocab).unwrap();
+
+    let err = DataBundle::new(&source, &LintLevels::default())
+        .err()
+        .unwrap();
+    let Diagnostics(diagnostics) = err.downcast_ref().unwrap();
+    let unmatched = diagnostics
+        .iter()
+        .find(|d| d.rule == "vocab-pronunciation")
+        .unwrap();
+    assert_eq!(unmatched.line, 3);
+    assert!(unmatched.message.contains("the word 言"));
+}
+
+#[test]
+fn raw_js_and_corpus_json_are_escaped() {
+    let source = copy_of_minimal_corpus("raw_js_and_corpus_json_are_escaped");
+    let phrases = std::fs::read_to_string(&source.phrase)
+        .unwrap()
+        .replace("I want to go.", "I want to go `${now}`."); // line 3
+    std::fs::write(&source.phrase, phrases).unwrap();
+    let config = Config {
+        source,
+        ..minimal_config("raw_js_and_corpus_json_are_escaped")
+    };
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+
+    spoonfed_pekzep::write_condensed_js(&data_bundle, &config, &build).unwrap();
+    let raw_js = std::fs::read_to_string(config.output.file("raw.js")).unwrap();
+    let line = raw_js.lines().nth(3).unwrap();
+    let row: serde_json::Value = serde_json::from_str(line.trim().trim_end_matches(',')).unwrap();
+    assert_eq!(row["english"], "I want to go `${now}`.");
+
+    spoonfed_pekzep::generate::corpus::r#gen(&data_bundle, &config, &build).unwrap();
+    let corpus: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(config.output.file("corpus.json")).unwrap())
+            .unwrap();
+    assert_eq!(corpus["schema_version"], 1);
+    assert_eq!(corpus["phrases"][2]["english"], "I want to go `${now}`.");
+    assert_eq!(corpus["phrases"][3]["decomposition"][0][3]["key"], "«足手»");
+    assert_eq!(corpus["vocab"].as_array().unwrap().len(), 12);
+}
+
+#[test]
+fn corpus_json_lists_the_readings_and_variants_of_the_chars() {
+    let source = copy_of_minimal_corpus("corpus_json_lists_the_readings_and_variants_of_the_chars");
+    let mut char_pronunciation = std::fs::read_to_string(&source.char_pronunciation).unwrap();
+    char_pronunciation.push_str("話\tzep1\t言\n");
+    std::fs::write(&source.char_pronunciation, char_pronunciation).unwrap();
+    let config = Config {
+        source,
+        ..minimal_config("corpus_json_lists_the_readings_and_variants_of_the_chars")
+    };
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+
+    spoonfed_pekzep::generate::corpus::r#gen(&data_bundle, &config, &build).unwrap();
+    let corpus: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(config.output.file("corpus.json")).unwrap())
+            .unwrap();
+    let char = |linzklar: &str| {
+        corpus["chars"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["linzklar"] == linzklar)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(
+        char("足"),
+        serde_json::json!({
+            "linzklar": "足",
+            "count": 1,
+            "readings": ["xi1"],
+            "contractions": [{"linzklars": "足手", "reading": "xiop1"}],
+            "variant_of": null,
+            "variants": [],
+        })
+    );
+    assert_eq!(char("言")["variants"], serde_json::json!(["話"]));
+}
+
+#[test]
+fn anki_deck_has_only_the_linked_recordings() {
+    let source = copy_of_minimal_corpus("anki_deck_has_only_the_linked_recordings");
+    // mua2 zep1 nan2 is recorded as oga
+    let phrases = std::fs::read_to_string(&source.phrase)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let mut cells = line.split('\t').collect::<Vec<_>>();
+            if cells[1] == "mua2 zep1 nan2?" {
+                cells[6] = "oga";
+            }
+            cells.join("\t") + "\n"
+        })
+        .collect::<String>();
+    std::fs::write(&source.phrase, phrases).unwrap();
+    let config = Config {
+        source,
+        ..minimal_config("anki_deck_has_only_the_linked_recordings")
+    };
+    let sounds = config.output.sounds_dir();
+    std::fs::create_dir_all(&sounds).unwrap();
+    // kait_kia1.oga is not linked from the spreadsheet
+    for name in ["mua2_zep1_nan2.oga", "kait_kia1.oga"] {
+        std::fs::write(sounds.join(name), "").unwrap();
+    }
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("anki_deck_has_only_the_linked_recordings")
+        .join("anki");
+    let build = Build::begin(&out).unwrap();
+
+    spoonfed_pekzep::generate::anki::export(&data_bundle, &config, &build, &out).unwrap();
+    let sentences = std::fs::read_to_string(out.join("sentences.txt")).unwrap();
+    assert!(sentences.contains("[sound:spoonfed_pekzep_mua2_zep1_nan2.oga]"));
+    assert!(!sentences.contains("kait_kia1.oga"));
+    assert!(!out.join("media/spoonfed_pekzep_kait_kia1.oga").exists());
+}
+
+#[test]
+fn undecomposed_lesson_phrases_are_warned() {
+    let source = copy_of_minimal_corpus("undecomposed_lesson_phrases_are_warned");
+    // kait kia1 (line 1) is not decomposed yet
+    let phrases = std::fs::read_to_string(&source.phrase)
+        .unwrap()
+        .replace("\t善日\t", "\t\t");
+    std::fs::write(&source.phrase, phrases).unwrap();
+    std::fs::write(
+        &source.lessons,
+        "title_en\ttitle_zh\ttitle_ja\tphrases\nGreetings\t问候\t挨拶\tkait_kia1..mua2_zep1_nan2\n",
+    )
+    .unwrap();
+    let config = Config {
+        source,
+        ..minimal_config("undecomposed_lesson_phrases_are_warned")
+    };
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let warned = data_bundle
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule == "undecomposed-lesson-phrase")
+        .map(|d| (d.file.clone(), d.line))
+        .collect::<Vec<_>>();
+    assert_eq!(warned, [(config.source.phrase.clone(), 1)]);
+
+    let build = Build::begin(&config.output.docs).unwrap();
+    std::fs::create_dir_all(config.output.lesson_dir()).unwrap();
+    spoonfed_pekzep::generate::worksheets::r#gen(&data_bundle, &config, &build).unwrap();
+}
+
+#[test]
+fn lessons_are_resolved_and_generated() {
+    let source = copy_of_minimal_corpus("lessons_are_resolved_and_generated");
+    std::fs::write(
+        &source.lessons,
+        "title_en\ttitle_zh\ttitle_ja\tphrases\nGreetings\t问候\t挨拶\tkait_kia1..mua2_zep1_nan2\nGoing\t去\t行く\tpai2_hia1_mok1, mua2_zep1_nan2\n",
+    )
+    .unwrap();
+    let config = Config {
+        source,
+        ..minimal_config("lessons_are_resolved_and_generated")
+    };
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+    assert_eq!(data_bundle.lessons[0].phrases, [0, 1]);
+    assert_eq!(data_bundle.lessons[1].phrases, [2, 1]);
+
+    std::fs::create_dir_all(config.output.lesson_dir()).unwrap();
+    spoonfed_pekzep::generate::lessons::r#gen(&data_bundle, &config, &build).unwrap();
+    let second = std::fs::read_to_string(config.output.lesson_dir().join("2.html")).unwrap();
+    // 言 was introduced in the first lesson
+    assert!(second.contains("../vocab/我.html"));
+    assert!(!second.contains("../vocab/言.html"));
+    assert!(second.contains(r#"<a href="../index.html">Next lesson →</a>"#));
+
+    spoonfed_pekzep::generate::worksheets::r#gen(&data_bundle, &config, &build).unwrap();
+    let worksheet =
+        std::fs::read_to_string(config.output.lesson_dir().join("2_worksheet.html")).unwrap();
+    let answers =
+        std::fs::read_to_string(config.output.lesson_dir().join("2_answers.html")).unwrap();
+    // every word is used once, so the first one is blanked
+    assert!(worksheet.contains("<li>(__________) hia1 mok1<br>"));
+    assert!(answers.contains("<li><u><strong>pai2</strong></u> hia1 mok1<br>"));
+
+    std::fs::write(
+        &config.source.lessons,
+        "title_en\ttitle_zh\ttitle_ja\tphrases\nGreetings\t问候\t挨拶\tkait_kia2\n",
+    )
+    .unwrap();
+    let err = DataBundle::new(&config.source, &config.lints)
+        .err()
+        .unwrap();
+    let Diagnostics(diagnostics) = err.downcast_ref().unwrap();
+    assert_eq!(
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| (d.line, d.rule.as_str()))
+            .collect::<Vec<_>>(),
+        [(2, "unknown-lesson-phrase")]
+    );
+}
+
+#[test]
+fn cloze_items_blank_the_word_and_offer_the_same_part_of_speech() {
+    let config = minimal_config("cloze_items_blank_the_word_and_offer_the_same_part_of_speech");
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+    let items = spoonfed_pekzep::generate::cloze::items(&data_bundle).unwrap();
+
+    let item = items.iter().find(|item| item.word == "汝").unwrap();
+    assert_eq!(item.phrase, "mua2_zep1_nan2");
+    assert_eq!(item.latin, ["___", "zep1", "nan2"]);
+    assert_eq!(item.hanzi, ["___", "言", "何"]);
+    assert_eq!(item.english, "What are you saying?");
+    // the answer and three distractors
+    assert_eq!(item.choices.len(), 4);
+    assert!(item.choices.contains(&"mua2".to_owned()));
+
+    // no other word is an interjection
+    assert!(items.iter().all(|item| item.word != "善日"));
+
+    spoonfed_pekzep::generate::cloze::r#gen(&data_bundle, &config, &build).unwrap();
+    let html = std::fs::read_to_string(config.output.file("cloze.html")).unwrap();
+    assert!(html.contains(r#""言":"<a href=\"./char/言.html\">"#));
+    // the items are only in cloze.json
+    assert!(html.contains(r#"fetch("cloze.json")"#));
+    assert!(!html.contains("mua2"));
+}
+
+#[test]
+fn quiz_only_uses_the_linzklars_with_images() {
+    let source = copy_of_minimal_corpus("quiz_only_uses_the_linzklars_with_images");
+    std::fs::create_dir_all(&source.char_img).unwrap();
+    for name in ["言.png", "之.png"] {
+        std::fs::write(source.char_img.join(name), b"").unwrap();
+    }
+    let config = Config {
+        source,
+        ..minimal_config("quiz_only_uses_the_linzklars_with_images")
+    };
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+    let data = spoonfed_pekzep::generate::quiz::QuizData::new(&data_bundle, &config).unwrap();
+    assert_eq!(
+        data.chars
+            .iter()
+            .map(|c| (c.linzklar.as_str(), c.syllables.clone()))
+            .collect::<Vec<_>>(),
+        [
+            ("之", vec!["a".to_owned()]),
+            ("言", vec!["zep1".to_owned()])
+        ]
+    );
+    // the row not yet reviewed is left out
+    assert_eq!(data.sentences.len(), 4);
+    assert!(data.sentences.iter().all(|s| s.audio.is_none()));
+
+    spoonfed_pekzep::generate::quiz::r#gen(&data_bundle, &config, &build).unwrap();
+    assert!(config.output.char_img_dir().join("言.png").exists());
+    let js = std::fs::read_to_string(config.output.file("quiz.js")).unwrap();
+    assert!(js.starts_with("const QUIZ_DATA = {\"chars\":"));
+}
+
+#[test]
+fn dictionary_is_indexed_by_gloss_and_components() {
+    let config = minimal_config("dictionary_is_indexed_by_gloss_and_components");
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+    let build = Build::begin(&config.output.docs).unwrap();
+    let dictionary =
+        spoonfed_pekzep::generate::dictionary::Dictionary::new(&data_bundle, &config, &build)
+            .unwrap();
+    let found = |index: &std::collections::BTreeMap<String, Vec<usize>>, term: &str| {
+        index[term]
+            .iter()
+            .map(|i| dictionary.entries[*i].key.clone())
+            .collect::<Vec<_>>()
+    };
+
+    // "to say"
+    assert_eq!(found(&dictionary.english, "say"), ["言"]);
+    assert_eq!(found(&dictionary.latin, "kia1"), ["善日"]);
+    assert_eq!(found(&dictionary.components, "人"), ["汝", "我"]);
+    assert!(!dictionary.components.contains_key("言"));
+}
+
+#[test]
+fn only_changed_pages_are_written_and_stale_ones_removed() {
+    let _ = std::fs::remove_dir_all(
+        Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("only_changed_pages_are_written_and_stale_ones_removed"),
+    );
+    let config = minimal_config("only_changed_pages_are_written_and_stale_ones_removed");
+    let folders = [config.output.phrase_dir(), config.output.char_img_dir()];
+    // the page of a phrase deleted from the input
+    let stale = config.output.phrase_dir().join("pai2_mok1.html");
+    std::fs::write(&stale, "").unwrap();
+    let data_bundle = DataBundle::new(&config.source, &config.lints).unwrap();
+
+    let build = Build::begin(&config.output.docs).unwrap();
+    spoonfed_pekzep::generate::phrases::r#gen(&data_bundle, &config, &build).unwrap();
+    let first = build.finish(&folders).unwrap();
+    assert!(first.written > 0);
+    assert_eq!((first.unchanged, first.removed), (0, 1));
+    assert!(!stale.exists());
+    assert!(config.output.file(manifest::FILE_NAME).exists());
+
+    let build = Build::begin(&config.output.docs).unwrap();
+    spoonfed_pekzep::generate::phrases::r#gen(&data_bundle, &config, &build).unwrap();
+    let second = build.finish(&folders).unwrap();
+    assert_eq!(
+        (second.written, second.unchanged, second.removed),
+        (0, first.written, 0)
+    );
+}
+
+#[test]
+fn preview_server_inserts_the_overlay_and_stays_inside_docs() {
+    use spoonfed_pekzep::diagnostic::Diagnostic;
+    use spoonfed_pekzep::serve::{self, Status};
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+
+    let docs = Path::new(env!("CARGO_TARGET_TMPDIR")).join("preview_server");
+    std::fs::create_dir_all(docs.join("char")).unwrap();
+    std::fs::write(docs.join("char/言.html"), "<html><body>言</body></html>").unwrap();
+    let status = Arc::new(Mutex::new(Status {
+        build_number: 3,
+        diagnostics: vec![Diagnostic::error(
+            "unknown-word",
+            Path::new("a.tsv"),
+            2,
+            "no such word",
+        )],
+        ..Status::default()
+    }));
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let get = |path: &str| {
+        let mut client = std::net::TcpStream::connect(address).unwrap();
+        write!(client, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        serve::handle(stream, &docs, &status).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let page = get("/char/%E8%A8%80.html");
+    assert!(page.starts_with("HTTP/1.1 200 OK"));
+    assert!(page.contains(r#"1 error(s), 0 warning(s)</a></div><script>"#));
+    assert!(page.ends_with("</body></html>"));
+    assert!(get(serve::DIAGNOSTICS_PATH).contains("error[unknown-word]: no such word"));
+    assert!(get(serve::BUILD_NUMBER_PATH).ends_with("\r\n\r\n3"));
+    assert!(get("/char/%E6%B1%9D.html").starts_with("HTTP/1.1 404"));
+    assert!(get("/../fixture_corpus.rs").starts_with("HTTP/1.1 403"));
+}
+
+#[test]
+fn denied_warnings_leave_docs_untouched() {
+    let docs = Path::new(env!("CARGO_TARGET_TMPDIR")).join("denied_warnings_leave_docs_untouched");
+    let _ = std::fs::remove_dir_all(&docs);
+    std::fs::create_dir_all(&docs).unwrap();
+    std::fs::write(docs.join("index.html"), "previous").unwrap();
+    std::fs::write(docs.join(manifest::FILE_NAME), "{\"files\": {}}").unwrap();
+
+    // the minimal corpus has no images, which is warned about
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_spoonfed_pekzep"))
+        .args(["--raw", "tests/fixtures/minimal", "--docs"])
+        .arg(&docs)
+        .args(["--deny-warnings", "build"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        std::fs::read_to_string(docs.join("index.html")).unwrap(),
+        "previous"
+    );
+    assert!(docs.join(manifest::FILE_NAME).exists());
+    assert!(!docs.join("phrase").exists());
+}